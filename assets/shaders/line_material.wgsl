@@ -1,13 +1,74 @@
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+
 struct LineMaterial {
     color: vec4<f32>,
+    width: f32,
+    dash: f32,
+    gap: f32,
 };
 
 @group(1) @binding(0)
 var<uniform> material: LineMaterial;
 
+// NOTE: Bindings must come before functions that use them!
+#import bevy_pbr::mesh_functions
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) other: vec3<f32>,
+    // side of the line, distance along it, 1.0 at a segment start and -1.0 at its end
+    @location(2) params: vec3<f32>,
+    @location(3) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) side: f32,
+    @location(2) distance: f32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let this_clip = mesh_position_local_to_clip(mesh.model, vec4<f32>(vertex.position, 1.0));
+    let other_clip = mesh_position_local_to_clip(mesh.model, vec4<f32>(vertex.other, 1.0));
+
+    let half_viewport = view.viewport.zw * 0.5;
+    let this_screen = this_clip.xy / this_clip.w * half_viewport;
+    let other_screen = other_clip.xy / other_clip.w * half_viewport;
+
+    var direction = (other_screen - this_screen) * vertex.params.z;
+    if (length(direction) < 0.0001) {
+        direction = vec2<f32>(1.0, 0.0);
+    }
+    direction = normalize(direction);
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    // One pixel of feathering outside the nominal width, for anti-aliasing
+    let half_width = material.width * 0.5 + 1.0;
+    let offset = normal * vertex.params.x * half_width / half_viewport * this_clip.w;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(this_clip.xy + offset, this_clip.zw);
+    out.color = vertex.color * material.color;
+    out.side = vertex.params.x;
+    out.distance = vertex.params.y;
+    return out;
+}
+
 @fragment
-fn fragment(
-    #import bevy_pbr::mesh_vertex_output
-) -> @location(0) vec4<f32> {
-    return material.color;
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (material.gap > 0.0) {
+        let period = material.dash + material.gap;
+        if (in.distance - floor(in.distance / period) * period > material.dash) {
+            discard;
+        }
+    }
+
+    let half_width = material.width * 0.5;
+    let from_centre = abs(in.side) * (half_width + 1.0);
+    let coverage = clamp(half_width + 0.5 - from_centre, 0.0, 1.0);
+
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
 }
@@ -0,0 +1,362 @@
+//! Engine hum, impact sounds and warning alarms for the player's ship.
+//!
+//! Deciding what to play is separate from playing it: the cue systems turn
+//! thrust, collision events and the player's `Orbit` into an engine level
+//! and `SoundCue` events, and a backend plays those through Bevy's audio or,
+//! for headless runs and tests, just records them.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::SparkSet;
+use crate::physics::gravity::Landed;
+use crate::physics::orbits::{periapsis, Orbit};
+use crate::planets::planet::Planet;
+use crate::planets::terrain::Terrain;
+use crate::ships::collisions::{ShipHitPlanet, ShipHitShip, TileDestroyed};
+use crate::ships::landing::surface_velocity;
+use crate::ships::propulsion::{engine_totals, fuel_totals, AppliedThrust};
+use crate::ships::ship::Player;
+use crate::ships::tiles::TileSet;
+
+/// Collision impulse that plays an impact at full volume.
+const FULL_IMPACT_IMPULSE: f32 = 100.0;
+/// How quickly the engine loop follows the throttle, per second.
+const ENGINE_RESPONSE: f32 = 8.0;
+/// Periapsis this close above the highest terrain sounds the alarm.
+const PERIAPSIS_CLEARANCE: f32 = 5.0;
+/// Seconds to the surface, at the current descent rate, that count as imminent.
+const IMPACT_WARNING: f32 = 10.0;
+const LOW_FUEL_FRACTION: f32 = 0.1;
+/// Seconds between repeats of an alarm while it stays raised.
+const ALARM_REPEAT: f32 = 2.0;
+
+/// Volumes from 0.0 to 1.0, each channel scaled by `master`.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub muted: bool,
+    pub master: f32,
+    pub engine: f32,
+    pub effects: f32,
+    pub alarms: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            muted: false,
+            master: 0.8,
+            engine: 0.6,
+            effects: 1.0,
+            alarms: 0.7,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// The volume to play a channel at, after master volume and muting.
+    pub fn level(&self, channel: f32) -> f32 {
+        if self.muted { 0.0 } else { self.master * channel }
+    }
+
+    pub fn volume(&self, sound: Sound) -> f32 {
+        match sound {
+            Sound::Impact | Sound::TileDestroyed => self.level(self.effects),
+            Sound::Alarm(_) => self.level(self.alarms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Alarm {
+    LowPeriapsis,
+    ImminentImpact,
+    LowFuel,
+}
+
+/// One-shot sounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Impact,
+    TileDestroyed,
+    Alarm(Alarm),
+}
+
+/// A sound to play once, at `volume` before `AudioSettings` are applied.
+#[derive(Debug, Clone, Copy)]
+pub struct SoundCue {
+    pub sound: Sound,
+    pub volume: f32,
+}
+
+/// The player's engine output, 0.0 to 1.0 of full thrust, smoothed.
+#[derive(Resource, Default)]
+pub struct EngineLevel(pub f32);
+
+/// Raised alarms, with the seconds until each one sounds again.
+#[derive(Resource, Default)]
+pub struct Alarms {
+    pub active: BTreeMap<Alarm, f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioBackend {
+    /// Play through Bevy's audio; needs `DefaultPlugins`
+    Bevy,
+    /// Play nothing, collecting cues in `PlayedSounds`
+    Null,
+}
+
+/// Cues the null backend would have played, at their final volume; silent
+/// ones, such as everything while muted, are left out.
+#[derive(Resource, Default)]
+pub struct PlayedSounds {
+    pub cues: Vec<SoundCue>,
+}
+
+/// Engine, impact and alarm sounds for the player's ship. Registers the ship
+/// events it listens to, so it runs with or without `SparkShipsPlugin`.
+pub struct SparkAudioPlugin {
+    pub backend: AudioBackend,
+}
+
+impl Plugin for SparkAudioPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .init_resource::<AudioSettings>()
+            .init_resource::<EngineLevel>()
+            .init_resource::<Alarms>()
+            .add_event::<SoundCue>()
+            .add_event::<ShipHitPlanet>()
+            .add_event::<ShipHitShip>()
+            .add_event::<TileDestroyed>()
+            .add_systems((
+                update_engine_level,
+                cue_impacts,
+                update_alarms,
+            ).in_set(SparkSet::Render));
+
+        match self.backend {
+            AudioBackend::Bevy => {
+                app
+                    .init_resource::<SoundAssets>()
+                    .init_resource::<EngineLoop>()
+                    .add_startup_system(start_engine_loop)
+                    .add_systems((
+                        set_engine_volume.after(update_engine_level),
+                        play_cues.after(cue_impacts).after(update_alarms),
+                    ).in_set(SparkSet::Render));
+            },
+            AudioBackend::Null => {
+                app
+                    .init_resource::<PlayedSounds>()
+                    .add_system(record_cues
+                                .in_set(SparkSet::Render)
+                                .after(cue_impacts)
+                                .after(update_alarms));
+            },
+        }
+    }
+}
+
+pub fn update_engine_level(
+    time: Res<Time>,
+    mut level: ResMut<EngineLevel>,
+    ships: Query<(&TileSet, &AppliedThrust), With<Player>>,
+) {
+    // Engines fall silent while the game is paused
+    if time.is_paused() {
+        level.0 = 0.0;
+        return;
+    }
+    let target = ships.get_single().map_or(0.0, |(tileset, applied)| {
+        let (_, max_thrust) = engine_totals(tileset);
+        if max_thrust > 0.0 { (applied.force.length() / max_thrust).min(1.0) } else { 0.0 }
+    });
+    level.0 += (target - level.0) * (time.delta_seconds() * ENGINE_RESPONSE).min(1.0);
+}
+
+/// Impacts involving the player, as loud as their impulse, and any tile the
+/// player loses.
+pub fn cue_impacts(
+    mut planet_hits: EventReader<ShipHitPlanet>,
+    mut ship_hits: EventReader<ShipHitShip>,
+    mut destroyed: EventReader<TileDestroyed>,
+    mut cues: EventWriter<SoundCue>,
+    players: Query<(), With<Player>>,
+) {
+    let impacts = planet_hits.iter().filter(|hit| players.contains(hit.ship)).map(|hit| hit.impulse)
+        .chain(ship_hits.iter().filter(|hit| players.contains(hit.ship) || players.contains(hit.other)).map(|hit| hit.impulse));
+    for impulse in impacts {
+        let volume = (impulse / FULL_IMPACT_IMPULSE).min(1.0);
+        if volume > 0.0 {
+            cues.send(SoundCue { sound: Sound::Impact, volume: volume });
+        }
+    }
+
+    for tile in destroyed.iter() {
+        if players.contains(tile.ship) {
+            cues.send(SoundCue { sound: Sound::TileDestroyed, volume: 1.0 });
+        }
+    }
+}
+
+/// Which alarms the player's ship should be sounding.
+fn raised_alarms(
+    tileset: &TileSet,
+    transform: &GlobalTransform,
+    velocity: &Velocity,
+    orbit: Option<&Orbit>,
+    planets: &Query<(&GlobalTransform, &Velocity, &Terrain), With<Planet>>,
+) -> Vec<Alarm> {
+    let mut alarms = Vec::new();
+
+    if let Some((orbit, (planet_transform, planet_velocity, terrain))) = orbit.and_then(|orbit| Some((orbit, planets.get(orbit.planet).ok()?))) {
+        let highest = terrain.heights.iter().copied().fold(0.0, f32::max);
+        let lowest = periapsis(orbit);
+        if lowest < highest + PERIAPSIS_CLEARANCE {
+            alarms.push(Alarm::LowPeriapsis);
+        }
+
+        let offset = (transform.translation() - planet_transform.translation()).truncate();
+        let altitude = offset.length() - terrain.height_at(offset.y.atan2(offset.x));
+        let relative = velocity.linvel - surface_velocity(planet_velocity, offset);
+        let descent = -relative.dot(offset.normalize_or_zero());
+        if lowest < highest && descent > 0.0 && altitude / descent < IMPACT_WARNING {
+            alarms.push(Alarm::ImminentImpact);
+        }
+    }
+
+    let (fuel, capacity) = fuel_totals(tileset);
+    if capacity > 0.0 && fuel / capacity < LOW_FUEL_FRACTION {
+        alarms.push(Alarm::LowFuel);
+    }
+
+    alarms
+}
+
+/// Raises and clears alarms for the player's ship, cueing each raised one
+/// every `ALARM_REPEAT` seconds. Landed ships only warn about fuel.
+pub fn update_alarms(
+    time: Res<Time>,
+    mut alarms: ResMut<Alarms>,
+    mut cues: EventWriter<SoundCue>,
+    ships: Query<(&TileSet, &GlobalTransform, &Velocity, Option<&Orbit>, Option<&Landed>), With<Player>>,
+    planets: Query<(&GlobalTransform, &Velocity, &Terrain), With<Planet>>,
+) {
+    let raised = match ships.get_single() {
+        Ok((tileset, transform, velocity, orbit, landed)) => {
+            raised_alarms(tileset, transform, velocity, orbit.filter(|_| landed.is_none()), &planets)
+        },
+        Err(_) => Vec::new(),
+    };
+
+    alarms.active.retain(|alarm, _| raised.contains(alarm));
+    for alarm in raised {
+        let repeat = alarms.active.entry(alarm).or_insert_with(|| {
+            info!("Alarm: {:?}", alarm);
+            0.0
+        });
+        *repeat -= time.delta_seconds();
+        if *repeat <= 0.0 {
+            *repeat = ALARM_REPEAT;
+            cues.send(SoundCue { sound: Sound::Alarm(alarm), volume: 1.0 });
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SoundAssets {
+    pub engine: Handle<AudioSource>,
+    pub impact: Handle<AudioSource>,
+    pub destroyed: Handle<AudioSource>,
+    pub low_periapsis: Handle<AudioSource>,
+    pub imminent_impact: Handle<AudioSource>,
+    pub low_fuel: Handle<AudioSource>,
+}
+
+impl FromWorld for SoundAssets {
+    fn from_world(world: &mut World) -> Self {
+        let server = world.resource::<AssetServer>();
+        SoundAssets {
+            engine: server.load("sounds/engine.wav"),
+            impact: server.load("sounds/impact.wav"),
+            destroyed: server.load("sounds/destroyed.wav"),
+            low_periapsis: server.load("sounds/alarm_periapsis.wav"),
+            imminent_impact: server.load("sounds/alarm_impact.wav"),
+            low_fuel: server.load("sounds/alarm_fuel.wav"),
+        }
+    }
+}
+
+impl SoundAssets {
+    pub fn get(&self, sound: Sound) -> Handle<AudioSource> {
+        match sound {
+            Sound::Impact => self.impact.clone(),
+            Sound::TileDestroyed => self.destroyed.clone(),
+            Sound::Alarm(Alarm::LowPeriapsis) => self.low_periapsis.clone(),
+            Sound::Alarm(Alarm::ImminentImpact) => self.imminent_impact.clone(),
+            Sound::Alarm(Alarm::LowFuel) => self.low_fuel.clone(),
+        }
+    }
+}
+
+/// The always-playing engine loop, silent at zero thrust.
+#[derive(Resource, Default)]
+pub struct EngineLoop {
+    pub sink: Option<Handle<AudioSink>>,
+}
+
+pub fn start_engine_loop(
+    audio: Res<Audio>,
+    sounds: Res<SoundAssets>,
+    sinks: Res<Assets<AudioSink>>,
+    mut engine: ResMut<EngineLoop>,
+) {
+    let sink = audio.play_with_settings(sounds.engine.clone(), PlaybackSettings::LOOP.with_volume(0.0));
+    engine.sink = Some(sinks.get_handle(sink));
+}
+
+pub fn set_engine_volume(
+    settings: Res<AudioSettings>,
+    level: Res<EngineLevel>,
+    engine: Res<EngineLoop>,
+    sinks: Res<Assets<AudioSink>>,
+) {
+    if let Some(sink) = engine.sink.as_ref().and_then(|sink| sinks.get(sink)) {
+        sink.set_volume(level.0 * settings.level(settings.engine));
+    }
+}
+
+pub fn play_cues(
+    audio: Res<Audio>,
+    sounds: Res<SoundAssets>,
+    settings: Res<AudioSettings>,
+    mut cues: EventReader<SoundCue>,
+) {
+    for cue in cues.iter() {
+        let volume = cue.volume * settings.volume(cue.sound);
+        if volume > 0.0 {
+            audio.play_with_settings(sounds.get(cue.sound), PlaybackSettings::ONCE.with_volume(volume));
+        }
+    }
+}
+
+pub fn record_cues(
+    settings: Res<AudioSettings>,
+    mut cues: EventReader<SoundCue>,
+    mut played: ResMut<PlayedSounds>,
+) {
+    for cue in cues.iter() {
+        let volume = cue.volume * settings.volume(cue.sound);
+        if volume > 0.0 {
+            played.cues.push(SoundCue { sound: cue.sound, volume: volume });
+        }
+    }
+}
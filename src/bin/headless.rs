@@ -0,0 +1,105 @@
+//! Run a scenario without a window or renderer and dump ship trajectories.
+//!
+//!   cargo run --bin headless -- config/scenarios/default.ron --seconds 60 --output out.csv
+
+use std::{
+    env,
+    process,
+};
+
+use bevy::{
+    prelude::*,
+    log::LogPlugin,
+};
+
+use spark::{
+    physics::trajectory::{record_trajectories, TrajectoryLog},
+    scenario::Scenario,
+    simulation::headless_app,
+};
+
+const USAGE: &str = "usage: headless <scenario.ron> [--seconds N] [--rate HZ] [--sample-every FRAMES] [--output FILE.csv|FILE.json]";
+
+struct Args {
+    scenario: String,
+    seconds: f32,
+    rate: f32,
+    sample_every: u32,
+    output: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        scenario: String::new(),
+        seconds: 60.0,
+        rate: 60.0,
+        sample_every: 1,
+        output: "trajectories.csv".to_string(),
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = |flag: &str| iter.next().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--seconds" => args.seconds = value("--seconds")?.parse().map_err(|e| format!("--seconds: {}", e))?,
+            "--rate" => args.rate = value("--rate")?.parse().map_err(|e| format!("--rate: {}", e))?,
+            "--sample-every" => args.sample_every = value("--sample-every")?.parse().map_err(|e| format!("--sample-every: {}", e))?,
+            "--output" => args.output = value("--output")?,
+            _ if arg.starts_with("--") => return Err(format!("unknown flag {}", arg)),
+            _ => args.scenario = arg.clone(),
+        }
+    }
+
+    if args.scenario.is_empty() {
+        return Err("no scenario given".to_string());
+    }
+    if args.rate <= 0.0 || args.sample_every == 0 {
+        return Err("--rate and --sample-every must be positive".to_string());
+    }
+
+    Ok(args)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let scenario = Scenario::load(&args.scenario).unwrap_or_else(|err| {
+        eprintln!("Couldn't load {}: {}", args.scenario, err);
+        process::exit(1);
+    });
+
+    let dt = 1.0 / args.rate;
+    let sample_every = args.sample_every;
+
+    let mut app = headless_app(scenario, dt);
+    app
+        .add_plugin(LogPlugin::default())
+        .add_system(record_trajectories
+                    .in_base_set(CoreSet::Last)
+                    .run_if(move |mut frame: Local<u32>| {
+                        *frame += 1;
+                        (*frame - 1) % sample_every == 0
+                    }));
+
+    let frames = (args.seconds * args.rate).ceil() as u32;
+    for _ in 0..frames {
+        app.update();
+    }
+
+    let log = app.world.resource::<TrajectoryLog>();
+    let result = if args.output.ends_with(".json") {
+        log.write_json(&args.output)
+    } else {
+        log.write_csv(&args.output)
+    };
+
+    if let Err(err) = result {
+        eprintln!("Couldn't write {}: {}", args.output, err);
+        process::exit(1);
+    }
+
+    info!("Wrote {} samples over {} frames to {}", log.samples.len(), frames, args.output);
+}
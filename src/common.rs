@@ -6,3 +6,36 @@ pub struct Mass {
 }
 
 pub const G: f32 = 6.67430e-11;
+
+/// Frame ordering shared by the Spark plugins. Each plugin only registers
+/// systems into these sets, so any subset of them can be embedded.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SparkSet {
+    /// Build colliders and components for newly added entities
+    Spawn,
+    /// Fit orbits to new orbitals
+    Orbits,
+    /// Read the fitted orbits: targets, firing solutions, mission goals
+    Navigation,
+    /// Write gravity, and move and spin bodies on rails
+    Gravity,
+    /// Add thrust and other forces on top of gravity, ahead of the Rapier step
+    Forces,
+    /// Sync meshes and markers with the simulation
+    Render,
+}
+
+impl SparkSet {
+    pub fn configure(app: &mut App) {
+        app
+            .configure_sets((
+                SparkSet::Spawn,
+                SparkSet::Orbits,
+                SparkSet::Navigation,
+                SparkSet::Gravity,
+                SparkSet::Forces,
+                SparkSet::Render,
+            ).chain())
+            .configure_set(SparkSet::Forces.before(bevy_rapier2d::plugin::PhysicsSet::StepSimulation));
+    }
+}
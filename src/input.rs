@@ -0,0 +1,161 @@
+//! Key bindings, loaded from `config/input.ron`. Axes pair two keys into a
+//! value from -1.0 to 1.0; actions are single keys. Both are looked up by
+//! the names used in that file.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+pub const BINDINGS_PATH: &str = "config/input.ron";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyCode),
+}
+
+impl Binding {
+    pub fn pressed(self, keys: &Input<KeyCode>) -> bool {
+        match self {
+            Binding::Key(key) => keys.pressed(key),
+        }
+    }
+
+    pub fn just_pressed(self, keys: &Input<KeyCode>) -> bool {
+        match self {
+            Binding::Key(key) => keys.just_pressed(key),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisBinding {
+    /// 1.0 while `pos` is held, -1.0 while `neg` is, 0.0 for both or neither
+    Emulated { pos: Binding, neg: Binding },
+}
+
+impl AxisBinding {
+    pub fn value(self, keys: &Input<KeyCode>) -> f32 {
+        match self {
+            AxisBinding::Emulated { pos, neg } => (pos.pressed(keys) as i32 - neg.pressed(keys) as i32) as f32,
+        }
+    }
+}
+
+#[derive(Resource, Deserialize, Debug, Clone)]
+pub struct InputBindings {
+    #[serde(default)]
+    pub axes: HashMap<String, AxisBinding>,
+    #[serde(default)]
+    pub actions: HashMap<String, Binding>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let axes = [
+            ("thrust", KeyCode::W, KeyCode::S),
+            ("turn", KeyCode::D, KeyCode::A),
+        ];
+        let actions = [
+            ("fire_guns", KeyCode::Space),
+            ("fire_missiles", KeyCode::R),
+            ("autopilot_off", KeyCode::Key0),
+            ("autopilot_prograde", KeyCode::Key1),
+            ("autopilot_retrograde", KeyCode::Key2),
+            ("autopilot_circularize_apoapsis", KeyCode::Key3),
+            ("autopilot_circularize_periapsis", KeyCode::Key4),
+            ("autopilot_execute_node", KeyCode::Key5),
+            ("target_selected", KeyCode::T),
+            ("plan_transfer", KeyCode::H),
+            ("plan_intercept", KeyCode::I),
+            ("undock", KeyCode::U),
+            ("mute", KeyCode::N),
+            ("volume_down", KeyCode::Minus),
+            ("volume_up", KeyCode::Equals),
+            ("toggle_map", KeyCode::M),
+            ("cycle_orbit_frame", KeyCode::F),
+            ("toggle_overlay", KeyCode::F3),
+            ("toggle_jacobi", KeyCode::J),
+            ("pause", KeyCode::Escape),
+        ];
+
+        InputBindings {
+            axes: axes.into_iter().map(|(name, pos, neg)| {
+                (name.to_string(), AxisBinding::Emulated { pos: Binding::Key(pos), neg: Binding::Key(neg) })
+            }).collect(),
+            actions: actions.into_iter().map(|(name, key)| (name.to_string(), Binding::Key(key))).collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    /// Bindings from `path`, with anything it leaves out bound as by default.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<InputBindings, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let mut bindings: InputBindings = ron::from_str(&source)?;
+
+        let defaults = InputBindings::default();
+        for (name, axis) in defaults.axes {
+            bindings.axes.entry(name).or_insert(axis);
+        }
+        for (name, action) in defaults.actions {
+            bindings.actions.entry(name).or_insert(action);
+        }
+        Ok(bindings)
+    }
+
+    /// The value of the axis called `name`, or 0.0 if there's no such axis.
+    pub fn axis(&self, keys: &Input<KeyCode>, name: &str) -> f32 {
+        self.axes.get(name).map_or(0.0, |axis| axis.value(keys))
+    }
+
+    pub fn pressed(&self, keys: &Input<KeyCode>, action: &str) -> bool {
+        self.actions.get(action).map_or(false, |binding| binding.pressed(keys))
+    }
+
+    pub fn just_pressed(&self, keys: &Input<KeyCode>, action: &str) -> bool {
+        self.actions.get(action).map_or(false, |binding| binding.just_pressed(keys))
+    }
+
+    /// The key bound to `action`, for messages telling the player what to press.
+    pub fn describe(&self, action: &str) -> String {
+        match self.actions.get(action) {
+            Some(Binding::Key(key)) => format!("{:?}", key),
+            None => "(unbound)".to_string(),
+        }
+    }
+}
+
+/// Loads `InputBindings` from `config/input.ron`, falling back to the
+/// default keys. The only place the resource is added; the states, render
+/// and flight control systems just read it.
+pub struct SparkInputPlugin;
+
+impl Plugin for SparkInputPlugin {
+    fn build(&self, app: &mut App) {
+        let bindings = InputBindings::load(BINDINGS_PATH).unwrap_or_else(|err| {
+            warn!("Couldn't load {}, using default keys: {}", BINDINGS_PATH, err);
+            InputBindings::default()
+        });
+        app.insert_resource(bindings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_bindings_are_the_defaults() {
+        let source = fs::read_to_string(BINDINGS_PATH).unwrap();
+        let shipped: InputBindings = ron::from_str(&source).unwrap();
+        let defaults = InputBindings::default();
+        assert_eq!(shipped.axes, defaults.axes);
+        assert_eq!(shipped.actions, defaults.actions);
+    }
+}
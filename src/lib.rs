@@ -0,0 +1,12 @@
+pub mod audio;
+pub mod common;
+pub mod input;
+pub mod mission;
+pub mod ships;
+pub mod planets;
+pub mod physics;
+pub mod render;
+pub mod scenario;
+pub mod settings;
+pub mod simulation;
+pub mod states;
@@ -1,68 +1,101 @@
+//! The windowed game. Pass a mission file to play it instead of free flight:
+//!
+//!   cargo run -- config/missions/first_orbit.ron
+
+use std::{
+    env,
+    process,
+};
+
 use bevy::{
     prelude::*,
-    app::{AppExit},
-    input::{
-        ButtonState,
-        keyboard::KeyboardInput,
-    },
     time::common_conditions::on_timer,
     utils::Duration,
 //    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
 };
 
-use bevy_rapier2d::prelude::*;
-
-mod common;
-mod ships;
-mod planets;
-mod physics;
-mod render;
+use spark::{
+    audio::{AudioBackend, SparkAudioPlugin},
+    input::{InputBindings, SparkInputPlugin},
+    mission::{spawn_mission_system, Mission, SparkMissionPlugin},
+    physics,
+    planets,
+    ships,
+    render::{SparkRenderPlugin, map::{FlightCamera, MapSelection}},
+    settings::{settings_path, Settings, SparkSettingsPlugin},
+    simulation::SimulationPlugins,
+    states::{simulation_running, SparkStatesPlugin},
+};
 
 fn main() {
+    let mission = env::args().nth(1).map(|path| {
+        Mission::load(&path).unwrap_or_else(|err| {
+            eprintln!("Couldn't load {}: {}", path, err);
+            process::exit(1);
+        })
+    });
 
-    App::new()
+    // Logging isn't up yet, so complain on stderr
+    let path = settings_path();
+    let settings = Settings::load(path).unwrap_or_else(|err| {
+        eprintln!("Couldn't load {}, using defaults: {}", path, err);
+        Settings::default()
+    });
+
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Spark".to_string(),
-                resolution: bevy::window::WindowResolution::new(1440.0, 900.0),
-                ..default()
-            }),
+            primary_window: Some(settings.display.window()),
             ..default()
-        }))
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugin(MaterialPlugin::<render::lines::LineMaterial>::default())
+        }).set(ImagePlugin::default_nearest()))
+        .add_plugins(SimulationPlugins)
+        .add_plugin(SparkInputPlugin)
+        .add_plugin(SparkStatesPlugin)
+        .add_plugin(SparkMissionPlugin)
+        .add_plugin(SparkRenderPlugin)
+        .add_plugin(SparkAudioPlugin { backend: AudioBackend::Bevy })
+        .add_plugin(SparkSettingsPlugin)
+        .insert_resource(settings)
 
-        .insert_resource(RapierConfiguration{
-            gravity: Vec2::ZERO,
-            ..default()
-        })
         .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
 
         .add_startup_system(setup)
-        .add_startup_system(planets::planet::make_planets_system)
-        .add_startup_system(ships::ship::make_ships_system)
-
-        .add_system(ships::tiles::make_tiles_system)
-        .add_system(physics::gravity::add_gravity)
-        .add_system(physics::gravity::calc_orbits)
-        .add_system(physics::gravity::render_orbits)
-        .add_system(physics::gravity::update_orbit_positions)
 
-        .add_system(physics::gravity::apply_gravity.before(bevy_rapier2d::plugin::PhysicsSet::StepSimulation))
-
-        .add_system(exit_on_esc_system)
+        .add_systems((
+            undock_on_key,
+            target_on_key,
+            transfer_on_key,
+            ships::control::player_control_system.before(spark::common::SparkSet::Forces),
+            ships::control::player_autopilot_system.before(spark::common::SparkSet::Forces),
+        ).distributive_run_if(simulation_running))
+        .add_system(volume_on_key)
 
         .add_system(physics::gravity::log_distances.run_if(on_timer(Duration::from_secs_f32(0.5))))
 
         // .add_plugin(LogDiagnosticsPlugin::default())
         // .add_plugin(FrameTimeDiagnosticsPlugin::default())
 
-//        .add_system(print_events)
-        .run();
+        .add_system(print_events.after(spark::common::SparkSet::Spawn));
+
+    match mission {
+        Some(mission) => {
+            app
+                .insert_resource(mission)
+                .add_startup_system(spawn_mission_system);
+        },
+        None => {
+            app
+                .add_startup_system(planets::planet::make_planets_system)
+                .add_startup_system(ships::ship::make_ships_system);
+        },
+    }
+
+    app.run();
 }
 
 fn setup(
     mut commands: Commands,
+    settings: Res<Settings>,
 ) {
 
     // commands.spawn_bundle(PointLightBundle {
@@ -75,44 +108,116 @@ fn setup(
     //     ..default()
     // });
 
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(0.0, 0.0, 100.0).looking_at(Vec3::ZERO, Vec3::Y),
-        projection: OrthographicProjection {
-            scale: 200.0,
-            scaling_mode: bevy::render::camera::ScalingMode::FixedVertical(1.0),
+    commands.spawn((
+        FlightCamera,
+        Camera3dBundle {
+            transform: Transform::from_xyz(0.0, 0.0, 100.0).looking_at(Vec3::ZERO, Vec3::Y),
+            projection: OrthographicProjection {
+                scale: settings.graphics.camera_zoom,
+                scaling_mode: bevy::render::camera::ScalingMode::FixedVertical(1.0),
+                ..default()
+            }.into(),
             ..default()
-        }.into(),
-        ..default()
-    });
+        },
+    ));
 
 }
 
-fn exit_on_esc_system(
-    mut keyboard_input_events: EventReader<KeyboardInput>,
-    mut app_exit_events: EventWriter<AppExit>,
+/// Targets the body selected on the map, or clears the target if nothing
+/// is selected.
+fn target_on_key(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    selection: Res<MapSelection>,
+    players: Query<Entity, With<ships::ship::Player>>,
+    names: Query<&Name>,
 ) {
-    for event in keyboard_input_events.iter() {
-        if let Some(key_code) = event.key_code {
-            if event.state == ButtonState::Pressed && key_code == KeyCode::Escape {
-                info!("Exiting");
-                app_exit_events.send(AppExit);
-            }
+    if !bindings.just_pressed(&keys, "target_selected") {
+        return;
+    }
+    for ship in players.iter() {
+        match selection.body.filter(|&body| body != ship) {
+            Some(target) => {
+                info!("Target: {}", names.get(target).map_or("?", |name| name.as_str()));
+                commands.entity(ship).insert(ships::targeting::Target { entity: target });
+            },
+            None => {
+                info!("Target cleared");
+                commands.entity(ship).remove::<(ships::targeting::Target, ships::targeting::TargetInfo)>();
+            },
         }
     }
 }
 
+/// Plans a Hohmann or bi-elliptic transfer to the player's target, or the
+/// best Lambert intercept of it.
+fn transfer_on_key(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    players: Query<(Entity, Option<&ships::targeting::Target>), With<ships::ship::Player>>,
+    mut requests: EventWriter<ships::transfer::TransferRequest>,
+) {
+    let kind = if bindings.just_pressed(&keys, "plan_transfer") {
+        ships::transfer::TransferKind::Coplanar
+    } else if bindings.just_pressed(&keys, "plan_intercept") {
+        ships::transfer::TransferKind::Intercept
+    } else {
+        return;
+    };
+    for (ship, target) in players.iter() {
+        let Some(target) = target else {
+            warn!("Pick a target first: select it on the map and press {}", bindings.describe("target_selected"));
+            continue;
+        };
+        requests.send(ships::transfer::TransferRequest { ship, target: target.entity, kind });
+    }
+}
+
+/// Mutes and unmutes, and steps the master volume.
+fn volume_on_key(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut settings: ResMut<Settings>,
+) {
+    // Only touch the settings on a key press, so they don't count as changed
+    if bindings.just_pressed(&keys, "mute") {
+        settings.audio.muted = !settings.audio.muted;
+        info!("Sound {}", if settings.audio.muted { "muted" } else { "on" });
+    }
+    let step = bindings.just_pressed(&keys, "volume_up") as i32 - bindings.just_pressed(&keys, "volume_down") as i32;
+    if step != 0 {
+        settings.audio.master = (settings.audio.master + step as f32 * 0.1).clamp(0.0, 1.0);
+        info!("Volume: {:.0}%", settings.audio.master * 100.0);
+    }
+}
 
-// fn print_events(
-//     mut events: EventReader<CollisionEvent>,
-// ) {
-//     for event in events.iter() {
-//         match event {
-//             CollisionEvent::Started(entity1, entity2, flags) => {
-//                 println!("Entity {:?} and {:?} started to collide: {:?}", entity1, entity2, flags)
-//             }
-//             CollisionEvent::Stopped(entity1, entity2, flags) => {
-//                 println!("Entity {:?} and {:?} stopped colliding: {:?}", entity1, entity2, flags)
-//             }
-//         }
-//     }
-// }
+fn undock_on_key(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    docked: Query<Entity, With<ships::docking::Docked>>,
+    mut requests: EventWriter<ships::docking::UndockRequest>,
+) {
+    if bindings.just_pressed(&keys, "undock") {
+        for ship in docked.iter() {
+            requests.send(ships::docking::UndockRequest { ship });
+        }
+    }
+}
+
+
+fn print_events(
+    mut planet_hits: EventReader<ships::collisions::ShipHitPlanet>,
+    mut ship_hits: EventReader<ships::collisions::ShipHitShip>,
+    mut destroyed: EventReader<ships::collisions::TileDestroyed>,
+) {
+    for hit in planet_hits.iter() {
+        debug!("{:?} hit planet {:?} at {:.2}, impulse {:.2}, tiles {:?}", hit.ship, hit.planet, hit.speed, hit.impulse, hit.tiles);
+    }
+    for hit in ship_hits.iter() {
+        debug!("{:?} hit ship {:?} at {:.2}, impulse {:.2}, tiles {:?} and {:?}", hit.ship, hit.other, hit.speed, hit.impulse, hit.tiles, hit.other_tiles);
+    }
+    for tile in destroyed.iter() {
+        debug!("{:?} lost its {:?} at {:?}", tile.ship, tile.kind, tile.pos);
+    }
+}
@@ -0,0 +1,348 @@
+//! Missions: a scenario to start from, objectives for the player's ship to
+//! complete in order, and triggers that fail the mission early.
+
+use std::{
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::common::SparkSet;
+use crate::physics::gravity::Landed;
+use crate::physics::orbits::{apoapsis, periapsis, Orbit};
+use crate::scenario::{spawn_scenario, Scenario};
+use crate::ships::collisions::{ShipHitShip, SHIP_DAMAGE_SPEED};
+use crate::ships::docking::Docked;
+use crate::ships::landing::ShipCrashed;
+use crate::ships::ship::Player;
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Objective {
+    /// Orbit the named body with periapsis and apoapsis radii within bounds
+    Orbit {
+        around: String,
+        periapsis: f32,
+        #[serde(default)]
+        apoapsis: Option<f32>,
+    },
+    /// Dock with the named ship
+    Dock { with: String },
+    /// Land on the named body
+    Land { on: String },
+}
+
+impl Objective {
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::Orbit { around, periapsis, apoapsis: None } => {
+                format!("Orbit {} with periapsis above {:.0}", around, periapsis)
+            },
+            Objective::Orbit { around, periapsis, apoapsis: Some(apoapsis) } => {
+                format!("Orbit {} between {:.0} and {:.0}", around, periapsis, apoapsis)
+            },
+            Objective::Dock { with } => format!("Dock with {}", with),
+            Objective::Land { on } => format!("Land on {}", on),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Failure {
+    /// The player's ship hits a planet too fast to land, or another ship
+    /// hard enough to do damage
+    Crash,
+    /// The player's ship loses every tile
+    Destroyed,
+    /// Seconds of sim time to finish in
+    TimeLimit(f32),
+}
+
+impl Failure {
+    pub fn describe(&self) -> String {
+        match self {
+            Failure::Crash => "Don't crash".to_string(),
+            Failure::Destroyed => "Don't lose the ship".to_string(),
+            Failure::TimeLimit(seconds) => format!("Finish within {:.0}s", seconds),
+        }
+    }
+}
+
+#[derive(Resource, Deserialize, Debug, Clone)]
+pub struct Mission {
+    pub name: String,
+    pub briefing: String,
+    pub scenario: Scenario,
+    pub objectives: Vec<Objective>,
+    #[serde(default)]
+    pub failures: Vec<Failure>,
+}
+
+impl Mission {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Mission, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let mission = ron::from_str(&source)?;
+        Ok(mission)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MissionStatus {
+    InProgress,
+    Succeeded,
+    Failed(String),
+}
+
+#[derive(Resource, Debug)]
+pub struct MissionProgress {
+    pub status: MissionStatus,
+    /// How many objectives are done; the next one is the current one
+    pub completed: usize,
+    /// Sim time since launch
+    pub elapsed: f32,
+}
+
+impl Default for MissionProgress {
+    fn default() -> Self {
+        MissionProgress {
+            status: MissionStatus::InProgress,
+            completed: 0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+/// Sent once, when the mission succeeds or fails.
+pub struct MissionEnded {
+    pub status: MissionStatus,
+}
+
+/// Evaluates the `Mission` resource, if there is one. Registers the ship
+/// events it listens to, so it runs with or without `SparkShipsPlugin`.
+pub struct SparkMissionPlugin;
+
+impl Plugin for SparkMissionPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .add_event::<MissionEnded>()
+            .add_event::<ShipCrashed>()
+            .add_event::<ShipHitShip>()
+            .init_resource::<MissionProgress>()
+            .add_system(evaluate_mission
+                        .in_set(SparkSet::Navigation)
+                        .run_if(resource_exists::<Mission>()));
+    }
+}
+
+pub fn spawn_mission_system(
+    mut commands: Commands,
+    mission: Res<Mission>,
+) {
+    spawn_scenario(&mut commands, &mission.scenario);
+    info!("Mission: {}", mission.name);
+}
+
+fn objective_met(
+    objective: &Objective,
+    orbit: Option<&Orbit>,
+    landed: Option<&Landed>,
+    docked: Option<&Docked>,
+    names: &Query<&Name>,
+) -> bool {
+    let named = |entity: Entity, name: &str| names.get(entity).map_or(false, |n| n.as_str() == name);
+    match objective {
+        Objective::Orbit { around, periapsis: lowest, apoapsis: highest } => orbit.map_or(false, |orbit| {
+            named(orbit.planet, around)
+                && orbit.eccentricity < 1.0
+                && periapsis(orbit) > *lowest
+                && highest.map_or(true, |highest| apoapsis(orbit) < highest)
+        }),
+        Objective::Dock { with } => docked.map_or(false, |docked| docked.parts.iter().any(|part| part.name == *with)),
+        Objective::Land { on } => landed.map_or(false, |landed| named(landed.planet, on)),
+    }
+}
+
+/// Checks the failure triggers, then ticks off objectives in order while
+/// the current one is met.
+pub fn evaluate_mission(
+    time: Res<Time>,
+    mission: Res<Mission>,
+    mut progress: ResMut<MissionProgress>,
+    mut crashes: EventReader<ShipCrashed>,
+    mut collisions: EventReader<ShipHitShip>,
+    mut ended: EventWriter<MissionEnded>,
+    mut launched: Local<bool>,
+    players: Query<(Entity, Option<&Orbit>, Option<&Landed>, Option<&Docked>), With<Player>>,
+    names: Query<&Name>,
+) {
+    if progress.status != MissionStatus::InProgress {
+        return;
+    }
+    progress.elapsed += time.delta_seconds();
+
+    let player = players.get_single().ok();
+    let is_player = |entity: Entity| player.map_or(false, |(ship, _, _, _)| entity == ship);
+    let crashed = crashes.iter().any(|crash| is_player(crash.ship));
+    let collided = collisions.iter()
+        .any(|hit| hit.speed >= SHIP_DAMAGE_SPEED && (is_player(hit.ship) || is_player(hit.other)));
+    *launched |= player.is_some();
+
+    let failure = mission.failures.iter().find_map(|failure| match failure {
+        Failure::Crash if crashed => Some("Crashed".to_string()),
+        Failure::Crash if collided => Some("Collided with another ship".to_string()),
+        Failure::Destroyed if *launched && player.is_none() => Some("Ship destroyed".to_string()),
+        Failure::TimeLimit(seconds) if progress.elapsed > *seconds => Some("Out of time".to_string()),
+        _ => None,
+    });
+
+    if failure.is_none() {
+        if let Some((_, orbit, landed, docked)) = player {
+            while let Some(objective) = mission.objectives.get(progress.completed) {
+                if !objective_met(objective, orbit, landed, docked, &names) {
+                    break;
+                }
+                info!("Objective complete: {}", objective.describe());
+                progress.completed += 1;
+            }
+        }
+    }
+
+    let status = match failure {
+        Some(reason) => MissionStatus::Failed(reason),
+        None if progress.completed >= mission.objectives.len() => MissionStatus::Succeeded,
+        None => return,
+    };
+    info!("Mission {}: {:?}", mission.name, status);
+    progress.status = status.clone();
+    ended.send(MissionEnded { status: status });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::SystemState, utils::Duration};
+
+    use super::*;
+    use crate::physics::orbits::{orbit_from_initial, OrbitalElements};
+    use crate::ships::docking::DockedPart;
+
+    const EARTH_MASS: f32 = 2.5e15;
+
+    fn mission(objectives: Vec<Objective>, failures: Vec<Failure>) -> Mission {
+        Mission {
+            name: "Test".to_string(),
+            briefing: String::new(),
+            scenario: Scenario { planets: vec![], ships: vec![], traffic: vec![] },
+            objectives: objectives,
+            failures: failures,
+        }
+    }
+
+    fn orbit_objective(periapsis: f32, apoapsis: Option<f32>) -> Objective {
+        Objective::Orbit { around: "Earth".to_string(), periapsis: periapsis, apoapsis: apoapsis }
+    }
+
+    /// Periapsis 48, apoapsis 72.
+    fn elliptic_orbit(earth: Entity) -> Orbit {
+        let elements = OrbitalElements { semimajor: 60.0, eccentricity: 0.2, ..default() };
+        let (r, v) = elements.state(EARTH_MASS);
+        orbit_from_initial(r, v, EARTH_MASS, earth, Vec3::ZERO, Duration::ZERO)
+    }
+
+    /// An app evaluating `mission`, with Earth and a player ship orbiting it.
+    fn mission_app(mission: Mission) -> (App, Entity) {
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .add_plugin(SparkMissionPlugin)
+            .insert_resource(mission);
+        let earth = app.world.spawn(Name::new("Earth")).id();
+        let orbit = elliptic_orbit(earth);
+        let player = app.world.spawn((Name::new("Player"), Player, orbit)).id();
+        (app, player)
+    }
+
+    fn hit(ship: Entity, speed: f32) -> ShipHitShip {
+        ShipHitShip {
+            ship: ship,
+            other: Entity::from_raw(1000),
+            relative_velocity: Vec2::new(speed, 0.0),
+            speed: speed,
+            impulse: 10.0,
+            tiles: vec![],
+            other_tiles: vec![],
+            point: Vec2::ZERO,
+        }
+    }
+
+    #[test]
+    fn orbits_must_fall_within_both_bounds() {
+        let mut world = World::new();
+        let earth = world.spawn(Name::new("Earth")).id();
+        let orbit = elliptic_orbit(earth);
+        let mut names: SystemState<Query<&Name>> = SystemState::new(&mut world);
+        let names = names.get(&world);
+        let met = |objective: Objective| objective_met(&objective, Some(&orbit), None, None, &names);
+
+        assert!(met(orbit_objective(45.0, None)));
+        assert!(met(orbit_objective(45.0, Some(75.0))));
+        assert!(!met(orbit_objective(50.0, None)), "periapsis is only 48");
+        assert!(!met(orbit_objective(45.0, Some(70.0))), "apoapsis is 72");
+        assert!(!met(Objective::Orbit { around: "Moon".to_string(), periapsis: 45.0, apoapsis: None }));
+        assert!(!objective_met(&orbit_objective(45.0, None), None, None, None, &names));
+    }
+
+    #[test]
+    fn escape_trajectories_are_not_orbits() {
+        let mut world = World::new();
+        let earth = world.spawn(Name::new("Earth")).id();
+        // Well over escape speed, about 82 at r = 50
+        let orbit = orbit_from_initial(Vec3::new(50.0, 0.0, 0.0), Vec3::new(0.0, 100.0, 0.0), EARTH_MASS, earth, Vec3::ZERO, Duration::ZERO);
+        assert!(orbit.eccentricity >= 1.0);
+
+        let mut names: SystemState<Query<&Name>> = SystemState::new(&mut world);
+        let names = names.get(&world);
+        assert!(!objective_met(&orbit_objective(10.0, None), Some(&orbit), None, None, &names));
+    }
+
+    #[test]
+    fn objectives_complete_in_order() {
+        let (mut app, player) = mission_app(mission(
+            vec![Objective::Dock { with: "Station".to_string() }, orbit_objective(45.0, None)],
+            vec![],
+        ));
+
+        // Already in the right orbit, but that waits on docking
+        app.update();
+        assert_eq!(app.world.resource::<MissionProgress>().completed, 0);
+
+        app.world.entity_mut(player).insert(Docked {
+            parts: vec![DockedPart { name: "Station".to_string(), tiles: vec![], host_port: (0, 0) }],
+        });
+        app.update();
+        let progress = app.world.resource::<MissionProgress>();
+        assert_eq!(progress.completed, 2);
+        assert_eq!(progress.status, MissionStatus::Succeeded);
+    }
+
+    #[test]
+    fn hard_ship_collisions_are_crashes() {
+        let failing = mission(vec![Objective::Land { on: "Earth".to_string() }], vec![Failure::Crash]);
+
+        let (mut app, player) = mission_app(failing.clone());
+        app.world.resource_mut::<Events<ShipHitShip>>().send(hit(player, SHIP_DAMAGE_SPEED * 0.9));
+        app.update();
+        assert_eq!(app.world.resource::<MissionProgress>().status, MissionStatus::InProgress);
+
+        let (mut app, player) = mission_app(failing);
+        app.world.resource_mut::<Events<ShipHitShip>>().send(hit(player, SHIP_DAMAGE_SPEED));
+        app.update();
+        assert_eq!(
+            app.world.resource::<MissionProgress>().status,
+            MissionStatus::Failed("Collided with another ship".to_string()),
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Compares each fitted `Orbit`'s Keplerian prediction with where Rapier
+//! actually has the body, to check the orbit maths and to catch orbits that
+//! have drifted (e.g. under thrust) and need re-fitting.
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    utils::Duration,
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::physics::gravity::{Landed, Orbital, OnRails};
+use crate::physics::orbits::*;
+use crate::planets::planet::Planet;
+
+const HISTORY_INTERVAL: f32 = 0.1;
+pub const HISTORY_LENGTH: usize = 60;
+
+/// Errors above which `OrbitDiverged` is sent.
+#[derive(Resource)]
+pub struct DivergenceThreshold {
+    pub position: f32,
+    pub velocity: f32,
+}
+
+impl Default for DivergenceThreshold {
+    fn default() -> Self {
+        DivergenceThreshold {
+            position: 1.0,
+            velocity: 0.5,
+        }
+    }
+}
+
+/// How far an orbital is from its `Orbit`'s prediction.
+#[derive(Component, Default)]
+pub struct OrbitError {
+    pub position: f32,
+    pub velocity: f32,
+    /// Recent (position, velocity) errors, oldest first.
+    pub history: VecDeque<(f32, f32)>,
+    since_sample: f32,
+    fitted_at: Duration,
+    diverged: bool,
+}
+
+/// Sent once each time an orbital passes the threshold, until its orbit is re-fitted.
+pub struct OrbitDiverged {
+    pub entity: Entity,
+    pub position_error: f32,
+    pub velocity_error: f32,
+}
+
+pub fn measure_divergence(
+    mut commands: Commands,
+    time: Res<Time>,
+    threshold: Res<DivergenceThreshold>,
+    mut diverged: EventWriter<OrbitDiverged>,
+    planets: Query<&Velocity, With<Planet>>,
+    mut orbitals: Query<(Entity, &GlobalTransform, &Velocity, &Orbit, Option<&mut OrbitError>), (With<Orbital>, Without<OnRails>, Without<Landed>)>,
+) {
+    let now = time.elapsed_seconds();
+
+    for (entity, transform, velocity, orbit, error) in orbitals.iter_mut() {
+        let Some(mut error) = error else {
+            commands.entity(entity).insert(OrbitError {
+                fitted_at: orbit.initial_time,
+                ..default()
+            });
+            continue;
+        };
+
+        if error.fitted_at != orbit.initial_time {
+            error.fitted_at = orbit.initial_time;
+            error.diverged = false;
+        }
+
+        let primary_velocity = planets.get(orbit.planet).map_or(Vec2::ZERO, |v| v.linvel);
+        let actual_velocity = (velocity.linvel - primary_velocity).extend(0.0);
+
+        error.position = transform.translation().distance(world_position_at(orbit, now));
+        error.velocity = actual_velocity.distance(velocity_at(orbit, now));
+
+        error.since_sample += time.delta_seconds();
+        if error.since_sample >= HISTORY_INTERVAL {
+            error.since_sample = 0.0;
+            let sample = (error.position, error.velocity);
+            error.history.push_back(sample);
+            if error.history.len() > HISTORY_LENGTH {
+                error.history.pop_front();
+            }
+        }
+
+        let over = error.position > threshold.position || error.velocity > threshold.velocity;
+        if over && !error.diverged {
+            error.diverged = true;
+            warn!("Orbit diverged: {:?} position {:.3} velocity {:.3}", entity, error.position, error.velocity);
+            diverged.send(OrbitDiverged {
+                entity: entity,
+                position_error: error.position,
+                velocity_error: error.velocity,
+            });
+        }
+    }
+}
+
+pub fn log_divergence(
+    errors: Query<(Option<&Name>, &OrbitError)>,
+) {
+    for (name, error) in errors.iter() {
+        let name = name.map_or("?", |n| n.as_str());
+        info!("Orbit error {}: position {:.3} velocity {:.3}", name, error.position, error.velocity);
+    }
+}
@@ -1,28 +1,126 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
 use crate::common::*;
 use crate::planets::planet::Planet;
 use crate::physics::orbits::*;
-
-use crate::render::lines::*;
+use crate::physics::divergence::OrbitDiverged;
 
 #[derive(Component)]
 pub struct Orbital;
 
+/// An `Orbital` that follows its fitted `Orbit` exactly instead of being
+/// integrated by Rapier. Use with `RigidBody::KinematicPositionBased`.
 #[derive(Component)]
-pub struct OrbitPath {
-    parent: Entity
-}
+pub struct OnRails;
 
+/// An `Orbital` resting on `planet`, carried along with its surface instead
+/// of orbiting. `local` is its transform in the planet's frame.
 #[derive(Component)]
-pub struct OrbitMarker {
-    parent: Entity
+pub struct Landed {
+    pub planet: Entity,
+    pub local: Transform,
+}
+
+/// Puts an orbital on the orbit given by `elements` around the planet named
+/// `around`, overriding its spawn position and velocity, as soon as that
+/// planet exists. Also the scenario file format.
+#[derive(Component, Deserialize, Debug, Clone)]
+pub struct PlaceInOrbit {
+    pub around: String,
+    pub elements: OrbitalElements,
+}
+
+pub type GravityQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static GlobalTransform,
+    &'static Mass,
+    Option<&'static Velocity>,
+    Option<&'static Orbit>,
+), With<Planet>>;
+
+/// A snapshot of one attracting body for the current frame.
+pub struct GravityBody {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+    pub soi: f32,
+}
+
+pub fn gravity_bodies(planets: &GravityQuery) -> Vec<GravityBody> {
+    planets.iter().filter_map(|(entity, transform, mass, velocity, orbit)| {
+        let soi = match orbit {
+            Some(orbit) => {
+                let (_, _, primary_mass, _, _) = planets.get(orbit.planet).ok()?;
+                sphere_of_influence(orbit.semimajor, mass.value, primary_mass.value)
+            },
+            None => f32::INFINITY,
+        };
+        let velocity = velocity.map_or(Vec2::ZERO, |v| v.linvel);
+        Some(GravityBody {
+            entity: entity,
+            position: transform.translation(),
+            velocity: velocity.extend(0.0),
+            mass: mass.value,
+            soi: soi,
+        })
+    }).collect()
+}
+
+/// The innermost sphere of influence containing `position`, ignoring `entity` itself.
+pub fn dominant_body(bodies: &[GravityBody], entity: Entity, position: Vec3) -> Option<&GravityBody> {
+    bodies.iter()
+        .filter(|body| body.entity != entity && body.position.distance(position) < body.soi)
+        .min_by(|a, b| a.soi.total_cmp(&b.soi))
+}
+
+/// Places pending orbitals, nested ones (a ship around a moon that is itself
+/// being placed) after their parents, all in the same frame.
+pub fn place_in_orbit(
+    mut commands: Commands,
+    bodies: Query<(&Name, &Transform, &Velocity, &Mass), (With<Planet>, Without<PlaceInOrbit>)>,
+    mut pending: Query<(Entity, Option<&Name>, Option<&Mass>, &PlaceInOrbit, &mut Transform, &mut GlobalTransform, &mut Velocity)>,
+) {
+    let mut states: Vec<(String, Vec3, Vec2, f32)> = bodies.iter()
+        .map(|(name, transform, velocity, mass)| (name.to_string(), transform.translation, velocity.linvel, mass.value))
+        .collect();
+    let mut placed: Vec<Entity> = Vec::new();
+
+    loop {
+        let mut progress = false;
+        for (entity, name, mass, place, mut transform, mut global, mut velocity) in pending.iter_mut() {
+            if placed.contains(&entity) {
+                continue;
+            }
+            let Some((_, position, parent_velocity, parent_mass)) = states.iter()
+                .find(|(body, _, _, _)| *body == place.around).cloned() else {
+                continue;
+            };
+
+            let (r, v) = place.elements.state(parent_mass);
+            transform.translation = position + r;
+            // Orbit fitting reads the global transform before it's next propagated
+            *global = GlobalTransform::from(*transform);
+            velocity.linvel = parent_velocity + v.truncate();
+
+            if let (Some(name), Some(mass)) = (name, mass) {
+                states.push((name.to_string(), transform.translation, velocity.linvel, mass.value));
+            }
+            commands.entity(entity).remove::<PlaceInOrbit>();
+            placed.push(entity);
+            progress = true;
+        }
+        if !progress {
+            break;
+        }
+    }
 }
 
 pub fn add_gravity(
     mut commands: Commands,
-    mut query: Query<Entity, Added<Orbital>>
+    mut query: Query<Entity, (Added<Orbital>, Without<OnRails>)>
 ) {
     for entity in query.iter_mut() {
         commands.entity(entity)
@@ -36,17 +134,22 @@ pub fn add_gravity(
 
 
 pub fn apply_gravity(
-    planets: Query<(&GlobalTransform, &Mass), With<Planet>>,
-    mut forces: Query<(&GlobalTransform, &ReadMassProperties, &mut ExternalForce), With<Orbital>>
+    planets: Query<(Entity, &GlobalTransform, &Mass), With<Planet>>,
+    mut forces: Query<(Entity, &GlobalTransform, &ReadMassProperties, &mut ExternalForce), (With<Orbital>, Without<OnRails>)>
 ) {
-    for (planet_pos, planet_mass) in planets.iter() {
-        for (ship_pos, mass_props, mut ext_force) in forces.iter_mut() {
-            let vector = planet_pos.translation() - ship_pos.translation();
-            let gravity = (G * planet_mass.value / vector.length_squared()) * vector.normalize();
-            let force = gravity * mass_props.0.mass;
+    for (ship, ship_pos, mass_props, mut ext_force) in forces.iter_mut() {
+        let mut gravity = Vec3::ZERO;
 
-            ext_force.force = Vec2::new(force.x, force.y);
+        for (planet, planet_pos, planet_mass) in planets.iter() {
+            if planet == ship {
+                continue;
+            }
+            let vector = planet_pos.translation() - ship_pos.translation();
+            gravity += (G * planet_mass.value / vector.length_squared()) * vector.normalize();
         }
+
+        let force = gravity * mass_props.0.mass;
+        ext_force.force = Vec2::new(force.x, force.y);
     }
 }
 
@@ -65,80 +168,66 @@ pub fn log_distances(
 }
 
 
+/// Fits an `Orbit` around each orbital's dominant body, and re-fits whenever
+/// that body changes (e.g. on entering a moon's sphere of influence) or the
+/// orbit no longer matches the body's motion.
 pub fn calc_orbits(
     mut commands: Commands,
     time: Res<Time>,
-    planets: Query<(Entity, &GlobalTransform, &Mass), With<Planet>>,
-    orbitals: Query<(Entity, &GlobalTransform, &Velocity), Added<Orbital>>,
+    mut diverged: EventReader<OrbitDiverged>,
+    planets: GravityQuery,
+    orbitals: Query<(Entity, &GlobalTransform, &Velocity, Option<&Orbit>, Option<&OnRails>), (With<Orbital>, Without<Landed>)>,
 ) {
-    let (planet, planet_transform, planet_mass) = planets.single();
+    let bodies = gravity_bodies(&planets);
+    let diverged: Vec<Entity> = diverged.iter().map(|event| event.entity).collect();
+
+    for (ship, ship_transform, ship_vel, current, rails) in orbitals.iter() {
+        if rails.is_some() && current.is_some() {
+            continue;
+        }
 
-    for (ship, ship_transform, ship_vel) in orbitals.iter() {
         let ship_pos = ship_transform.translation();
-        let planet_pos = planet_transform.translation();
+        let Some(primary) = dominant_body(&bodies, ship, ship_pos) else {
+            continue;
+        };
+
+        if current.map_or(false, |orbit| orbit.planet == primary.entity) && !diverged.contains(&ship) {
+            continue;
+        }
 
-        let r = ship_pos - planet_pos;
-        let v = Vec3::new(ship_vel.linvel.x, ship_vel.linvel.y, 0.0);
+        let r = ship_pos - primary.position;
+        let v = ship_vel.linvel.extend(0.0) - primary.velocity;
 
-        info!("Relative ship pos: {:?}", ship_pos);
+        info!("Relative ship pos: {:?}", r);
 
-        let orbit = orbit_from_initial(r, v, planet_mass.value, planet, planet_pos, time.raw_elapsed());
+        let orbit = orbit_from_initial(r, v, primary.mass, primary.entity, primary.position, time.elapsed());
         commands.entity(ship).insert(orbit);
     }
 }
 
 
-pub fn render_orbits(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<LineMaterial>>,
-    orbits: Query<(Entity, &Orbit), Added<Orbit>>
+pub fn update_orbit_focus(
+    mut orbits: Query<&mut Orbit>,
+    planets: Query<&GlobalTransform, With<Planet>>,
 ) {
-    for (entity, orbit) in orbits.iter() {
-        let c = orbit.eccentricity * orbit.semimajor;
-        let periapsis = orbit.semimajor - c;
-        let apoapsis = orbit.semimajor * 2.0 - periapsis;
-
-        info!("Orbit: {:?}, {:?}, {:?}", periapsis, apoapsis, orbit.argument.to_degrees());
-
-        let points = orbit_to_points(orbit, 128);
-
-        commands.spawn((
-            OrbitPath{ parent: entity },
-            MaterialMeshBundle {
-                mesh: meshes.add(Mesh::from(LineStrip {
-                    points: points,
-                })),
-                material: materials.add(LineMaterial { color: Color::GREEN }),
-                ..default()
-            }));
-
-        commands.spawn((
-            OrbitMarker{ parent: entity },
-            MaterialMeshBundle {
-                mesh: meshes.add(shape::Circle::new(0.5).into()).into(),
-                material: materials.add(LineMaterial { color: Color::RED }),
-                transform: Transform::from_translation(Vec3::new(0., 0., 0.)),
-                ..default()
+    for mut orbit in orbits.iter_mut() {
+        if let Ok(planet_transform) = planets.get(orbit.planet) {
+            let focus = planet_transform.translation();
+            if orbit.focus != focus {
+                orbit.focus = focus;
             }
-        ));
-
+        }
     }
 }
 
 
-pub fn update_orbit_positions(
+/// Moves bodies on rails along their orbits.
+pub fn move_on_rails(
     time: Res<Time>,
-    orbits: Query<&Orbit>,
-    mut markers: Query<(&OrbitMarker, &mut Transform)>
+    mut rails: Query<(&Orbit, &mut Transform), With<OnRails>>,
 ) {
-    for (marker, mut transform) in markers.iter_mut() {
-        if let Ok(orbit) = orbits.get(marker.parent) {
-            let time_offset = time.raw_elapsed() - orbit.initial_time;
-            let (x, y, z) = calculate_position_at_time(orbit, time_offset.as_secs_f32());
-            transform.translation = Vec3::new(x, z, 0.);
-        } else {
-            error!("No orbit! {:?}", marker.parent);
-        }
+    let now = time.elapsed_seconds();
+    for (orbit, mut transform) in rails.iter_mut() {
+        transform.translation = world_position_at(orbit, now);
     }
 }
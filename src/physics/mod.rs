@@ -1,2 +1,59 @@
+use bevy::{
+    prelude::*,
+    time::common_conditions::on_timer,
+    utils::Duration,
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::common::SparkSet;
+
+pub mod divergence;
 pub mod gravity;
 pub mod orbits;
+pub mod trajectory;
+
+/// Rapier with zero global gravity, plus point-mass gravity from every `Planet`
+/// onto every `Orbital`.
+pub struct SparkPhysicsPlugin;
+
+impl Plugin for SparkPhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .insert_resource(RapierConfiguration{
+                gravity: Vec2::ZERO,
+                ..default()
+            })
+
+            .add_system(gravity::add_gravity.in_set(SparkSet::Spawn))
+            .add_system(gravity::apply_gravity.in_set(SparkSet::Gravity));
+    }
+}
+
+/// Keplerian orbit fitting for `Orbital`s, propagation of bodies on rails and
+/// monitoring of how far the fits drift from Rapier. Needs Rapier's
+/// `Velocity`, so goes alongside `SparkPhysicsPlugin`.
+pub struct SparkOrbitsPlugin;
+
+impl Plugin for SparkOrbitsPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .add_event::<divergence::OrbitDiverged>()
+            .init_resource::<divergence::DivergenceThreshold>()
+            .add_systems((
+                divergence::measure_divergence.before(gravity::calc_orbits),
+                gravity::calc_orbits,
+                gravity::update_orbit_focus.after(gravity::calc_orbits),
+            ).in_set(SparkSet::Orbits))
+            .add_system(gravity::place_in_orbit.in_set(SparkSet::Spawn))
+            .add_system(gravity::move_on_rails.in_set(SparkSet::Gravity))
+            .add_system(divergence::log_divergence
+                        .in_set(SparkSet::Orbits)
+                        .after(divergence::measure_divergence)
+                        .run_if(on_timer(Duration::from_secs(1))));
+    }
+}
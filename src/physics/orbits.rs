@@ -1,12 +1,23 @@
 use std::f32::consts::*;
 
 use bevy::{
+    math::DVec3,
     prelude::*,
     utils::Duration
 };
+use serde::Deserialize;
 
 use crate::common::*;
 
+const COLLINEAR_ITERATIONS: usize = 20;
+/// Keeps the potential finite at the bodies' centres.
+const MIN_POTENTIAL_RADIUS: f32 = 1.0e-3;
+const LAMBERT_ITERATIONS: usize = 100;
+const APPROACH_SAMPLES: usize = 200;
+const APPROACH_REFINEMENTS: usize = 30;
+/// Lambert solutions whose flight time is off by more than this fraction are rejected.
+const LAMBERT_TOLERANCE: f64 = 1.0e-3;
+
 #[derive(Component)]
 pub struct Orbit {
     pub planet: Entity,
@@ -18,7 +29,8 @@ pub struct Orbit {
     pub clockwise: bool,
 
     pub initial_time: Duration,
-    pub initial_true_anomaly: f32
+    pub initial_true_anomaly: f32,
+    pub initial_mean_anomaly: f32
 }
 
 
@@ -38,9 +50,15 @@ pub fn orbit_from_initial(r: Vec3, v: Vec3, m: f32, planet: Entity, focus: Vec3,
     let argument = e[1].atan2(e[0]);
     let period = TAU * (a.powi(3) / mu).sqrt();
 
-    let h2u = h.length().powi(2) / mu;
+    // Measure the anomaly from periapsis in the direction of travel. Near-circular
+    // orbits have no meaningful eccentricity vector, so use the argument's axis.
+    let periapsis_dir = Vec3::new(argument.cos(), argument.sin(), 0.0);
+    let r_dir = r.normalize();
+    let ccw_anomaly = periapsis_dir.cross(r_dir).z.atan2(periapsis_dir.dot(r_dir));
+    let initial_true_anomaly = if clockwise { -ccw_anomaly } else { ccw_anomaly };
 
-    let initial_true_anomaly = (((h2u / r0) - 1.) - e0).acos() - FRAC_PI_2;
+    let initial_eccentric_anomaly = 2.0 * (((1.0 - e0) / (1.0 + e0)).sqrt() * (initial_true_anomaly / 2.0).tan()).atan();
+    let initial_mean_anomaly = (initial_eccentric_anomaly - e0 * initial_eccentric_anomaly.sin()).rem_euclid(TAU);
 
     return Orbit {
         planet: planet,
@@ -52,21 +70,449 @@ pub fn orbit_from_initial(r: Vec3, v: Vec3, m: f32, planet: Entity, focus: Vec3,
         clockwise: clockwise,
 
         initial_time: time,
-        initial_true_anomaly: initial_true_anomaly
+        initial_true_anomaly: initial_true_anomaly,
+        initial_mean_anomaly: initial_mean_anomaly
     };
 }
 
+/// The shape of an orbit and where on it a body starts. Angles are in
+/// radians, the argument from +x and the anomaly from periapsis.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct OrbitalElements {
+    pub semimajor: f32,
+    #[serde(default)]
+    pub eccentricity: f32,
+    #[serde(default)]
+    pub argument: f32,
+    #[serde(default)]
+    pub true_anomaly: f32,
+    #[serde(default)]
+    pub clockwise: bool,
+}
+
+impl OrbitalElements {
+    /// Position and velocity relative to a primary of `primary_mass`.
+    pub fn state(&self, primary_mass: f32) -> (Vec3, Vec3) {
+        state_from_elements(
+            primary_mass,
+            self.semimajor,
+            self.eccentricity,
+            self.argument,
+            self.true_anomaly,
+            self.clockwise,
+        )
+    }
+}
+
+/// Position and velocity relative to the primary on the orbit with these
+/// elements, `true_anomaly` from periapsis in the direction of travel. The
+/// inverse of `orbit_from_initial`.
+pub fn state_from_elements(
+    primary_mass: f32,
+    semimajor: f32,
+    eccentricity: f32,
+    argument: f32,
+    true_anomaly: f32,
+    clockwise: bool,
+) -> (Vec3, Vec3) {
+    let mu = G * primary_mass;
+    let semi_rectum = semimajor * (1.0 - eccentricity.powi(2));
+    let r = semi_rectum / (1.0 + eccentricity * true_anomaly.cos());
+    let speed = (mu / semi_rectum).sqrt();
+
+    // In the perifocal frame, periapsis along +x; clockwise orbits are the mirror image
+    let flip = if clockwise { -1.0 } else { 1.0 };
+    let position = Vec2::new(r * true_anomaly.cos(), flip * r * true_anomaly.sin());
+    let velocity = Vec2::new(-speed * true_anomaly.sin(), flip * speed * (eccentricity + true_anomaly.cos()));
+
+    let rotation = Vec2::from_angle(argument);
+    (rotation.rotate(position).extend(0.0), rotation.rotate(velocity).extend(0.0))
+}
+
+/// Points around the orbit relative to its focus, in the same frame as
+/// `calculate_position_at_time`.
 pub fn orbit_to_points(orbit: &Orbit, points: u32) -> Vec<Vec3> {
-    let phi = orbit.argument;
     let step = TAU / (points - 1) as f32;
     let semi_rectum = orbit.semimajor * (1.0 - orbit.eccentricity.powi(2));
     return (0..points).map(|i| {
         let theta = i as f32 * step;
-        let radius = semi_rectum / (1.0 - (orbit.eccentricity * (theta - phi).cos()));
-        return Vec3::new(radius * theta.sin(), radius * theta.cos(), 0.0);
+        let radius = semi_rectum / (1.0 + (orbit.eccentricity * theta.cos()));
+        let angle = theta + orbit.argument;
+        return Vec3::new(radius * angle.cos(), radius * angle.sin(), 0.0);
     }).collect::<Vec<Vec3>>();
 }
 
+/// World position at `time` (seconds on the same clock as `initial_time`),
+/// assuming the focus stays where it is now.
+pub fn world_position_at(orbit: &Orbit, time: f32) -> Vec3 {
+    let (x, _, z) = calculate_position_at_time(orbit, time - orbit.initial_time.as_secs_f32());
+    orbit.focus + Vec3::new(x, z, 0.0)
+}
+
+/// Velocity at `time` relative to the focus, by central difference of
+/// `world_position_at`.
+pub fn velocity_at(orbit: &Orbit, time: f32) -> Vec3 {
+    const STEP: f32 = 0.05;
+    let ahead = world_position_at(orbit, time + STEP);
+    let behind = world_position_at(orbit, time - STEP);
+    (ahead - behind) / (2.0 * STEP)
+}
+
+pub fn periapsis(orbit: &Orbit) -> f32 {
+    orbit.semimajor * (1.0 - orbit.eccentricity)
+}
+
+/// Infinite for open orbits.
+pub fn apoapsis(orbit: &Orbit) -> f32 {
+    if orbit.eccentricity < 1.0 { orbit.semimajor * (1.0 + orbit.eccentricity) } else { f32::INFINITY }
+}
+
+/// Seconds from `time` until the orbit next reaches `mean_anomaly`.
+pub fn time_to_mean_anomaly(orbit: &Orbit, time: f32, mean_anomaly: f32) -> f32 {
+    let mean_motion = calculate_mean_motion(orbit.period);
+    let now = calculate_mean_anomaly(mean_motion, orbit.initial_mean_anomaly, time - orbit.initial_time.as_secs_f32());
+    (mean_anomaly - now).rem_euclid(TAU) / mean_motion
+}
+
+/// Speed at distance `r` from the focus, by vis-viva.
+pub fn speed_at_radius(orbit: &Orbit, primary_mass: f32, r: f32) -> f32 {
+    (G * primary_mass * (2.0 / r - 1.0 / orbit.semimajor)).max(0.0).sqrt()
+}
+
+pub fn circular_speed(primary_mass: f32, r: f32) -> f32 {
+    (G * primary_mass / r).sqrt()
+}
+
+pub fn sphere_of_influence(semimajor: f32, mass: f32, primary_mass: f32) -> f32 {
+    semimajor * (mass / primary_mass).powf(0.4)
+}
+
+/// When, within `horizon` seconds of `time`, a body on `orbit` comes closest
+/// to one on `target` around the same primary, or to the primary itself if
+/// `target` is `None`; and how close it gets.
+pub fn closest_approach(orbit: &Orbit, target: Option<&Orbit>, time: f32, horizon: f32) -> (f32, f32) {
+    let separation = |t: f32| {
+        let other = target.map_or(orbit.focus, |target| world_position_at(target, t));
+        world_position_at(orbit, t).distance(other)
+    };
+
+    // Coarse samples find the right dip, then a ternary search pins it down
+    let step = horizon / APPROACH_SAMPLES as f32;
+    let nearest = (0..=APPROACH_SAMPLES)
+        .map(|i| time + step * i as f32)
+        .min_by(|a, b| separation(*a).total_cmp(&separation(*b)))
+        .unwrap_or(time);
+    let (mut low, mut high) = ((nearest - step).max(time), (nearest + step).min(time + horizon));
+    for _ in 0..APPROACH_REFINEMENTS {
+        let (a, b) = (low + (high - low) / 3.0, high - (high - low) / 3.0);
+        if separation(a) < separation(b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    let t = (low + high) / 2.0;
+    (t, separation(t))
+}
+
+/// Burns between two coplanar circular orbits around the same primary, each
+/// as (seconds after the first burn, prograde delta-v); negative is retrograde.
+#[derive(Debug, Clone, Default)]
+pub struct CoplanarTransfer {
+    pub burns: Vec<(f32, f32)>,
+    /// How far round the primary the ship goes between the first and last burn
+    pub sweep: f32,
+}
+
+impl CoplanarTransfer {
+    pub fn delta_v(&self) -> f32 {
+        self.burns.iter().map(|(_, dv)| dv.abs()).sum()
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.burns.last().map_or(0.0, |(time, _)| *time)
+    }
+}
+
+/// Half an ellipse from `r1` to `r2`, burning at each end.
+pub fn hohmann_transfer(primary_mass: f32, r1: f32, r2: f32) -> CoplanarTransfer {
+    let mu = G * primary_mass;
+    let a = (r1 + r2) / 2.0;
+    let vis_viva = |r: f32| (mu * (2.0 / r - 1.0 / a)).sqrt();
+
+    CoplanarTransfer {
+        burns: vec![
+            (0.0, vis_viva(r1) - circular_speed(primary_mass, r1)),
+            (PI * (a.powi(3) / mu).sqrt(), circular_speed(primary_mass, r2) - vis_viva(r2)),
+        ],
+        sweep: PI,
+    }
+}
+
+/// Out to `apoapsis` on one half ellipse and back down to `r2` on another.
+/// Cheaper than a Hohmann transfer when `r2` is over about twelve times `r1`.
+pub fn bi_elliptic_transfer(primary_mass: f32, r1: f32, r2: f32, apoapsis: f32) -> CoplanarTransfer {
+    let mu = G * primary_mass;
+    let (a1, a2) = ((r1 + apoapsis) / 2.0, (r2 + apoapsis) / 2.0);
+    let vis_viva = |r: f32, a: f32| (mu * (2.0 / r - 1.0 / a)).sqrt();
+    let (t1, t2) = (PI * (a1.powi(3) / mu).sqrt(), PI * (a2.powi(3) / mu).sqrt());
+
+    CoplanarTransfer {
+        burns: vec![
+            (0.0, vis_viva(r1, a1) - circular_speed(primary_mass, r1)),
+            (t1, vis_viva(apoapsis, a2) - vis_viva(apoapsis, a1)),
+            (t1 + t2, circular_speed(primary_mass, r2) - vis_viva(r2, a2)),
+        ],
+        sweep: TAU,
+    }
+}
+
+/// Stumpff functions C(z) and S(z).
+fn stumpff(z: f64) -> (f64, f64) {
+    if z > 1.0e-6 {
+        let root = z.sqrt();
+        ((1.0 - root.cos()) / z, (root - root.sin()) / root.powi(3))
+    } else if z < -1.0e-6 {
+        let root = (-z).sqrt();
+        ((root.cosh() - 1.0) / -z, (root.sinh() - root) / root.powi(3))
+    } else {
+        (0.5, 1.0 / 6.0)
+    }
+}
+
+/// Velocities at either end of the path from `r1` to `r2` that takes
+/// `time_of_flight` seconds going less than once round, positions relative to
+/// the primary. Universal variables, after Curtis. `None` when the ends are
+/// in line with the primary, where the plane of the path is undefined.
+pub fn lambert(primary_mass: f32, r1: Vec3, r2: Vec3, time_of_flight: f32, clockwise: bool) -> Option<(Vec3, Vec3)> {
+    let mu = (G * primary_mass) as f64;
+    let tof = time_of_flight as f64;
+    let (r1, r2) = (r1.as_dvec3(), r2.as_dvec3());
+    let (l1, l2) = (r1.length(), r2.length());
+    if l1 <= 0.0 || l2 <= 0.0 || tof <= 0.0 {
+        return None;
+    }
+
+    let cos = (r1.dot(r2) / (l1 * l2)).clamp(-1.0, 1.0);
+    let mut angle = cos.acos();
+    if (r1.cross(r2).z < 0.0) != clockwise {
+        angle = std::f64::consts::TAU - angle;
+    }
+    let a = angle.sin() * (l1 * l2 / (1.0 - cos)).sqrt();
+    if !a.is_finite() || a.abs() < 1.0e-9 {
+        return None;
+    }
+
+    let y = |z: f64| {
+        let (c, s) = stumpff(z);
+        l1 + l2 + a * (z * s - 1.0) / c.sqrt()
+    };
+    let flight_time = |z: f64| {
+        let (c, s) = stumpff(z);
+        let y = y(z);
+        if y < 0.0 {
+            return None;
+        }
+        let chi = (y / c).sqrt();
+        Some((chi.powi(3) * s + a * y.sqrt()) / mu.sqrt())
+    };
+
+    // Flight time rises with z, without bound as z nears (2 pi)^2
+    let (mut low, mut high) = (-400.0, std::f64::consts::TAU.powi(2));
+    for _ in 0..LAMBERT_ITERATIONS {
+        let mid = (low + high) / 2.0;
+        match flight_time(mid) {
+            Some(t) if t >= tof => high = mid,
+            _ => low = mid,
+        }
+    }
+    let z = (low + high) / 2.0;
+    let t = flight_time(z)?;
+    if ((t - tof) / tof).abs() > LAMBERT_TOLERANCE {
+        return None;
+    }
+
+    let y = y(z);
+    let f = 1.0 - y / l1;
+    let g = a * (y / mu).sqrt();
+    let g_dot = 1.0 - y / l2;
+    let v1: DVec3 = (r2 - r1 * f) / g;
+    let v2: DVec3 = (r2 * g_dot - r1) / g;
+    Some((v1.as_vec3(), v2.as_vec3()))
+}
+
+/// The burns, as world-space delta-v, to leave `orbit` at `departure` and
+/// match `target` `flight_time` seconds later. Both orbits must share a primary.
+pub fn intercept(orbit: &Orbit, target: &Orbit, primary_mass: f32, departure: f32, flight_time: f32) -> Option<(Vec3, Vec3)> {
+    let arrival = departure + flight_time;
+    let r1 = world_position_at(orbit, departure) - orbit.focus;
+    let r2 = world_position_at(target, arrival) - target.focus;
+    let (v1, v2) = lambert(primary_mass, r1, r2, flight_time, orbit.clockwise)?;
+    Some((v1 - velocity_at(orbit, departure), velocity_at(target, arrival) - v2))
+}
+
+/// Total intercept delta-v over a grid of departure and flight times, `None`
+/// where there's no solution.
+#[derive(Debug, Clone, Default)]
+pub struct Porkchop {
+    pub departures: Vec<f32>,
+    pub flight_times: Vec<f32>,
+    /// Indexed by departure, then flight time
+    pub delta_v: Vec<Vec<Option<f32>>>,
+}
+
+impl Porkchop {
+    pub fn new(orbit: &Orbit, target: &Orbit, primary_mass: f32, departures: Vec<f32>, flight_times: Vec<f32>) -> Porkchop {
+        let delta_v = departures.iter().map(|&departure| {
+            flight_times.iter().map(|&flight_time| {
+                let (first, second) = intercept(orbit, target, primary_mass, departure, flight_time)?;
+                Some(first.length() + second.length())
+            }).collect()
+        }).collect();
+
+        Porkchop {
+            departures: departures,
+            flight_times: flight_times,
+            delta_v: delta_v,
+        }
+    }
+
+    /// The cheapest (departure, flight time, delta-v).
+    pub fn best(&self) -> Option<(f32, f32, f32)> {
+        self.departures.iter().zip(self.delta_v.iter())
+            .flat_map(|(&departure, row)| {
+                self.flight_times.iter().zip(row.iter())
+                    .filter_map(move |(&flight_time, dv)| Some((departure, flight_time, (*dv)?)))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+    }
+
+    /// A text table of delta-v, a row per departure and a column per flight
+    /// time, with times relative to `now`.
+    pub fn table(&self, now: f32) -> String {
+        let mut table = format!("{:>8} |", "depart");
+        for flight_time in self.flight_times.iter() {
+            table += &format!(" {:>6.1}s", flight_time);
+        }
+        for (departure, row) in self.departures.iter().zip(self.delta_v.iter()) {
+            table += &format!("\n{:>7.1}s |", departure - now);
+            for dv in row {
+                table += &match dv {
+                    Some(dv) => format!(" {:>7.1}", dv),
+                    None => format!(" {:>7}", "-"),
+                };
+            }
+        }
+        table
+    }
+}
+
+/// A secondary on a roughly circular orbit about its primary, as the
+/// circular restricted three-body problem sees it: everything else is too
+/// light to matter, and the frame turns with the pair about its barycentre.
+#[derive(Debug, Clone, Copy)]
+pub struct RestrictedThreeBody {
+    pub primary: Vec3,
+    pub secondary: Vec3,
+    pub primary_mass: f32,
+    pub secondary_mass: f32,
+    /// Velocity of the barycentre
+    pub velocity: Vec3,
+    /// Rate the pair turns at, counter-clockwise positive
+    pub angular_velocity: f32,
+}
+
+impl RestrictedThreeBody {
+    pub fn new(
+        primary: Vec3,
+        primary_velocity: Vec3,
+        primary_mass: f32,
+        secondary: Vec3,
+        secondary_velocity: Vec3,
+        secondary_mass: f32,
+    ) -> RestrictedThreeBody {
+        let total = primary_mass + secondary_mass;
+        let r = secondary - primary;
+        let v = secondary_velocity - primary_velocity;
+        RestrictedThreeBody {
+            primary: primary,
+            secondary: secondary,
+            primary_mass: primary_mass,
+            secondary_mass: secondary_mass,
+            velocity: (primary_velocity * primary_mass + secondary_velocity * secondary_mass) / total,
+            angular_velocity: r.cross(v).z / r.length_squared().max(f32::EPSILON),
+        }
+    }
+
+    /// The secondary's share of the total mass.
+    pub fn mass_ratio(&self) -> f32 {
+        self.secondary_mass / (self.primary_mass + self.secondary_mass)
+    }
+
+    pub fn barycentre(&self) -> Vec3 {
+        self.primary.lerp(self.secondary, self.mass_ratio())
+    }
+
+    pub fn separation(&self) -> f32 {
+        self.primary.distance(self.secondary)
+    }
+
+    /// L1 to L5 in world space. L1 sits between the bodies, L2 beyond the
+    /// secondary and L3 opposite it; L4 leads the secondary by 60 degrees and
+    /// L5 trails it.
+    pub fn lagrange_points(&self) -> [Vec3; 5] {
+        let mu = self.mass_ratio();
+        let d = self.separation();
+        let axis = (self.secondary - self.primary).normalize_or_zero();
+        let ahead = if self.angular_velocity < 0.0 { -1.0 } else { 1.0 };
+        let side = Vec3::new(-axis.y, axis.x, 0.0) * ahead;
+        let at = |x: f32, y: f32| self.barycentre() + (axis * x + side * y) * d;
+
+        // Collinear points, in units of the separation from the barycentre,
+        // from the usual Hill-sphere guesses
+        let hill = (mu / 3.0).cbrt();
+        let collinear = |guess: f32| {
+            let mut x = guess;
+            for _ in 0..COLLINEAR_ITERATIONS {
+                let (r1, r2) = (x + mu, x - 1.0 + mu);
+                let f = x - (1.0 - mu) * r1 / r1.abs().powi(3) - mu * r2 / r2.abs().powi(3);
+                let slope = 1.0 + 2.0 * (1.0 - mu) / r1.abs().powi(3) + 2.0 * mu / r2.abs().powi(3);
+                x -= f / slope;
+            }
+            x
+        };
+        let triangle = 3f32.sqrt() / 2.0;
+
+        [
+            at(collinear(1.0 - mu - hill), 0.0),
+            at(collinear(1.0 - mu + hill), 0.0),
+            at(collinear(-1.0 - 5.0 * mu / 12.0), 0.0),
+            at(0.5 - mu, triangle),
+            at(0.5 - mu, -triangle),
+        ]
+    }
+
+    /// Gravitational plus centrifugal potential per unit mass in the turning
+    /// frame, positive and highest near the bodies.
+    pub fn effective_potential(&self, position: Vec3) -> f32 {
+        let r1 = position.distance(self.primary).max(MIN_POTENTIAL_RADIUS);
+        let r2 = position.distance(self.secondary).max(MIN_POTENTIAL_RADIUS);
+        0.5 * self.angular_velocity.powi(2) * position.distance_squared(self.barycentre())
+            + G * self.primary_mass / r1
+            + G * self.secondary_mass / r2
+    }
+
+    /// The Jacobi constant of a body at `position` moving at `velocity`, both
+    /// in world space. It's conserved in the turning frame, and the body can
+    /// only reach places where twice the effective potential is at least this.
+    pub fn jacobi_constant(&self, position: Vec3, velocity: Vec3) -> f32 {
+        let offset = position - self.barycentre();
+        let turning = Vec3::new(-offset.y, offset.x, 0.0) * self.angular_velocity;
+        let relative = velocity - self.velocity - turning;
+        2.0 * self.effective_potential(position) - relative.length_squared()
+    }
+}
+
 // https://github.com/atbentley/bevy_mod_orbits/blob/main/src/math.rs
 
 #[inline]
@@ -75,7 +521,7 @@ pub fn calculate_position_at_time(
     time: f32,
 ) -> (f32, f32, f32) {
     let mean_motion = calculate_mean_motion(orbit.period);
-    let mean_anomaly = calculate_mean_anomaly(mean_motion, orbit.initial_true_anomaly, time);
+    let mean_anomaly = calculate_mean_anomaly(mean_motion, orbit.initial_mean_anomaly, time);
     let eccentric_anomaly = calculate_eccentric_anomaly(orbit.eccentricity, mean_anomaly);
     let mut true_anomaly = calculate_true_anomaly(orbit.eccentricity, eccentric_anomaly);
     if !orbit.clockwise { true_anomaly *= -1. };
@@ -141,3 +587,127 @@ pub fn calculate_position(
 
     (rotated_x, 0.0, rotated_z)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_round_trip_through_orbit_fitting() {
+        let mass = 2.5e15;
+        for clockwise in [false, true] {
+            let elements = OrbitalElements {
+                semimajor: 60.0,
+                eccentricity: 0.2,
+                argument: 0.5,
+                true_anomaly: 1.0,
+                clockwise: clockwise,
+            };
+            let (r, v) = elements.state(mass);
+            let orbit = orbit_from_initial(r, v, mass, Entity::from_raw(0), Vec3::ZERO, Duration::ZERO);
+
+            assert!((orbit.semimajor - 60.0).abs() < 1.0e-2, "semimajor {}", orbit.semimajor);
+            assert!((orbit.eccentricity - 0.2).abs() < 1.0e-4, "eccentricity {}", orbit.eccentricity);
+            assert!((orbit.argument - 0.5).abs() < 1.0e-4, "argument {}", orbit.argument);
+            assert!((orbit.initial_true_anomaly - 1.0).abs() < 1.0e-4, "true anomaly {}", orbit.initial_true_anomaly);
+            assert_eq!(orbit.clockwise, clockwise);
+        }
+    }
+
+    /// A moon a tenth of its planet's mass on a circular orbit of radius 100,
+    /// both moving about their barycentre.
+    fn moon_system() -> RestrictedThreeBody {
+        let (planet_mass, moon_mass, separation) = (1.0e15, 1.0e14, 100.0);
+        let total = planet_mass + moon_mass;
+        let rate = (G * total / separation.powi(3)).sqrt();
+        let primary = Vec3::new(20.0, -30.0, 0.0);
+        RestrictedThreeBody::new(
+            primary,
+            Vec3::new(0.0, -rate * separation * moon_mass / total, 0.0),
+            planet_mass,
+            primary + Vec3::new(separation, 0.0, 0.0),
+            Vec3::new(0.0, rate * separation * planet_mass / total, 0.0),
+            moon_mass,
+        )
+    }
+
+    #[test]
+    fn lagrange_points_are_equilibria() {
+        let pair = moon_system();
+        let step = 0.2;
+        let gradient = |at: Vec3| {
+            let dx = Vec3::new(step, 0.0, 0.0);
+            let dy = Vec3::new(0.0, step, 0.0);
+            Vec2::new(
+                pair.effective_potential(at + dx) - pair.effective_potential(at - dx),
+                pair.effective_potential(at + dy) - pair.effective_potential(at - dy),
+            ) / (2.0 * step)
+        };
+        // The primary's pull at the secondary's distance
+        let scale = G * pair.primary_mass / pair.separation().powi(2);
+
+        for (i, point) in pair.lagrange_points().iter().enumerate() {
+            let acceleration = gradient(*point).length();
+            assert!(acceleration < 2.0e-3 * scale, "L{} at {:?}: {} against {}", i + 1, point, acceleration, scale);
+        }
+    }
+
+    #[test]
+    fn triangular_points_are_one_separation_from_both() {
+        let pair = moon_system();
+        let d = pair.separation();
+        let points = pair.lagrange_points();
+        for point in [points[3], points[4]] {
+            assert!((point.distance(pair.primary) - d).abs() < 1.0e-3 * d, "{:?}", point);
+            assert!((point.distance(pair.secondary) - d).abs() < 1.0e-3 * d, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn hohmann_matches_the_closed_form() {
+        let mass = 2.5e15;
+        let mu = G * mass;
+        let (r1, r2) = (10.0, 20.0);
+        let transfer = hohmann_transfer(mass, r1, r2);
+
+        let expected = (mu / r1).sqrt() * ((2.0 * r2 / (r1 + r2)).sqrt() - 1.0)
+            + (mu / r2).sqrt() * (1.0 - (2.0 * r1 / (r1 + r2)).sqrt());
+        assert!((transfer.delta_v() - expected).abs() < 1.0e-4 * expected, "{} against {}", transfer.delta_v(), expected);
+
+        let half_period = PI * (((r1 + r2) / 2.0).powi(3) / mu).sqrt();
+        assert!((transfer.duration() - half_period).abs() < 1.0e-4 * half_period);
+    }
+
+    #[test]
+    fn bi_elliptic_wins_only_past_the_crossover() {
+        // With the apoapsis far out, the break-even ratio tends to about 11.94
+        let mass = 2.5e15;
+        let r1 = 10.0;
+        let cost = |ratio: f32| {
+            let r2 = r1 * ratio;
+            (hohmann_transfer(mass, r1, r2).delta_v(), bi_elliptic_transfer(mass, r1, r2, r2 * 1000.0).delta_v())
+        };
+
+        let (hohmann, bi_elliptic) = cost(11.0);
+        assert!(hohmann < bi_elliptic, "ratio 11: {} against {}", hohmann, bi_elliptic);
+        let (hohmann, bi_elliptic) = cost(12.5);
+        assert!(bi_elliptic < hohmann, "ratio 12.5: {} against {}", bi_elliptic, hohmann);
+    }
+
+    #[test]
+    fn lambert_paths_arrive_on_time() {
+        let mass = 2.5e15;
+        let r1 = Vec3::new(50.0, 0.0, 0.0);
+        for (r2, time_of_flight, clockwise) in [
+            (Vec3::new(0.0, 80.0, 0.0), 2.5, false),
+            (Vec3::new(0.0, -80.0, 0.0), 2.5, true),
+            (Vec3::new(-70.0, 20.0, 0.0), 3.0, false),
+            (Vec3::new(0.0, 80.0, 0.0), 6.0, true),
+        ] {
+            let (v1, _) = lambert(mass, r1, r2, time_of_flight, clockwise).unwrap();
+            let orbit = orbit_from_initial(r1, v1, mass, Entity::from_raw(0), Vec3::ZERO, Duration::ZERO);
+            let arrival = world_position_at(&orbit, time_of_flight);
+            assert!(arrival.distance(r2) < 5.0e-3 * r2.length(), "aiming for {:?}, got to {:?}", r2, arrival);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Serialize;
+
+use crate::physics::gravity::Orbital;
+
+/// A CSV field holding `text` verbatim, whatever commas, quotes or newlines
+/// it contains.
+fn csv_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TrajectorySample {
+    pub time: f32,
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct TrajectoryLog {
+    pub samples: Vec<TrajectorySample>,
+}
+
+impl TrajectoryLog {
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "time,name,x,y,vx,vy")?;
+        for s in self.samples.iter() {
+            writeln!(out, "{},{},{},{},{},{}", s.time, csv_quote(&s.name), s.x, s.y, s.vx, s.vy)?;
+        }
+        out.flush()
+    }
+
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(out, &self.samples)?;
+        Ok(())
+    }
+}
+
+pub fn record_trajectories(
+    time: Res<Time>,
+    mut log: ResMut<TrajectoryLog>,
+    orbitals: Query<(&Name, &GlobalTransform, &Velocity), With<Orbital>>,
+) {
+    let now = time.elapsed_seconds();
+    for (name, transform, velocity) in orbitals.iter() {
+        let pos = transform.translation();
+        log.samples.push(TrajectorySample {
+            time: now,
+            name: name.to_string(),
+            x: pos.x,
+            y: pos.y,
+            vx: velocity.linvel.x,
+            vy: velocity.linvel.y,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_names_are_quoted() {
+        assert_eq!(csv_quote("Player"), "\"Player\"");
+        assert_eq!(csv_quote("Ship, \"the\" best"), "\"Ship, \"\"the\"\" best\"");
+    }
+}
@@ -1 +1,19 @@
+use bevy::prelude::*;
+
+use crate::common::SparkSet;
+
 pub mod planet;
+pub mod terrain;
+
+/// Planet spin for bodies on rails, and landed bodies riding the surface.
+pub struct SparkPlanetsPlugin;
+
+impl Plugin for SparkPlanetsPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .add_system(planet::spin_planets.in_set(SparkSet::Gravity))
+            .add_system(planet::follow_surface.in_set(SparkSet::Forces));
+    }
+}
@@ -1,45 +1,183 @@
+use std::f32::consts::TAU;
+
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
 use crate::common::*;
+use crate::physics::gravity::{Landed, Orbital, OnRails, PlaceInOrbit};
+use crate::planets::terrain::*;
 
 #[derive(Component)]
-pub struct Planet;
+pub struct Planet {
+    pub radius: f32,
+    /// Seconds per turn, counter-clockwise; negative spins clockwise and zero
+    /// not at all.
+    pub rotation_period: f32,
+}
+
+impl Planet {
+    pub fn angular_velocity(&self) -> f32 {
+        if self.rotation_period == 0.0 { 0.0 } else { TAU / self.rotation_period }
+    }
+}
+
+/// How a planet moves: pinned in place, on analytic rails around its dominant
+/// body, or integrated by Rapier under gravity like a ship.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum PlanetMotion {
+    #[default]
+    Fixed,
+    Rails,
+    Dynamic,
+}
+
+/// Everything needed to spawn a planet; also the scenario file format.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlanetSpec {
+    pub name: String,
+    pub mass: f32,
+    pub radius: f32,
+    /// Terrain height variation as a fraction of `radius`
+    #[serde(default)]
+    pub roughness: f32,
+    /// See `Planet::rotation_period`
+    #[serde(default)]
+    pub rotation_period: f32,
+    #[serde(default)]
+    pub position: (f32, f32),
+    #[serde(default)]
+    pub velocity: (f32, f32),
+    /// Starts on this orbit instead, ignoring `position` and `velocity`
+    #[serde(default)]
+    pub orbit: Option<PlaceInOrbit>,
+    #[serde(default)]
+    pub motion: PlanetMotion,
+}
 
 pub fn make_planets_system(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    commands.spawn((
-        Planet,
-        Name::new("Earth"),
-        Mass { value: 2500000000000000.0 },
-        RigidBody::Fixed,
-        Collider::ball(20.0),
+    spawn_planet(&mut commands, &PlanetSpec {
+        name: "Earth".to_string(),
+        mass: 2500000000000000.0,
+        radius: 20.0,
+        roughness: 0.03,
+        rotation_period: 60.0,
+        position: (0.0, 0.0),
+        velocity: (0.0, 0.0),
+        orbit: None,
+        motion: PlanetMotion::Fixed,
+    });
+    info!("Added planet");
+}
+
+pub fn spawn_planet(
+    commands: &mut Commands,
+    spec: &PlanetSpec,
+) -> Entity {
+    let terrain = Terrain::generate(spec.radius, spec.roughness, name_seed(&spec.name));
+    let planet_component = Planet {
+        radius: spec.radius,
+        rotation_period: spec.rotation_period,
+    };
+    let spin = planet_component.angular_velocity();
+    let (x, y) = spec.position;
+    let (vx, vy) = spec.velocity;
+
+    let mut planet = commands.spawn((
+        planet_component,
+        Name::new(spec.name.clone()),
+        Mass { value: spec.mass },
+        terrain.collider(),
+        // Ships come to rest on the surface instead of bouncing off it
+        Restitution {
+            coefficient: 0.0,
+            combine_rule: CoefficientCombineRule::Min
+        },
+        Friction {
+            coefficient: 1.0,
+            combine_rule: CoefficientCombineRule::Max
+        },
+        ActiveEvents::COLLISION_EVENTS,
+        terrain,
+        Velocity {
+            linvel: Vec2::new(vx, vy),
+            angvel: spin,
+        },
         SpatialBundle {
             transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 0.0),
+                translation: Vec3::new(x, y, 0.0),
                 ..default()
             },
             ..default()
         }
-    )).with_children(|parent| {
-        parent.spawn(
+    ));
+
+    if let Some(orbit) = &spec.orbit {
+        planet.insert(orbit.clone());
+    }
+
+    match spec.motion {
+        PlanetMotion::Fixed if spin != 0.0 => {
+            // Stays put but spins, with a surface velocity contacts can see
+            planet.insert(RigidBody::KinematicVelocityBased);
+        },
+        PlanetMotion::Fixed => {
+            planet.insert(RigidBody::Fixed);
+        },
+        PlanetMotion::Rails => {
+            planet.insert((RigidBody::KinematicPositionBased, Orbital, OnRails));
+        },
+        PlanetMotion::Dynamic => {
+            planet.insert((RigidBody::Dynamic, Orbital));
+        },
+    }
+
+    planet.id()
+}
+
+/// Turns planets on rails; Rapier spins the rest from their angular velocity.
+pub fn spin_planets(
+    time: Res<Time>,
+    mut planets: Query<(&Planet, &mut Transform), With<OnRails>>,
+) {
+    let now = time.elapsed_seconds();
+    for (planet, mut transform) in planets.iter_mut() {
+        transform.rotation = Quat::from_rotation_z(planet.angular_velocity() * now);
+    }
+}
+
+/// Carries landed bodies along with their planet's surface.
+pub fn follow_surface(
+    planets: Query<&Transform, (With<Planet>, Without<Landed>)>,
+    mut landed: Query<(&Landed, &mut Transform)>,
+) {
+    for (landed, mut transform) in landed.iter_mut() {
+        if let Ok(planet_transform) = planets.get(landed.planet) {
+            *transform = planet_transform.mul_transform(landed.local);
+        }
+    }
+}
+
+pub fn render_planets_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    planets: Query<(Entity, &Terrain), Added<Planet>>,
+) {
+    for (entity, terrain) in planets.iter() {
+        let surface = commands.spawn(
             PbrBundle {
-                mesh: meshes.add(Mesh::try_from(shape::Circle {
-                    radius: 20.0,
-                    vertices: 128,
-                }).unwrap()),
+                mesh: meshes.add(terrain.mesh()),
                 material: materials.add(StandardMaterial {
                     base_color: Color::hex("ffd891").unwrap(),
                     unlit: true,
                     ..default()
                 }),
                 ..default()
-            });
-    });
-
-    info!("Added planet");
+            }).id();
 
+        commands.entity(entity).add_child(surface);
+    }
 }
@@ -0,0 +1,126 @@
+//! Planet surfaces as a heightmap around the circumference, shared by the
+//! collider and the surface mesh so what you see is what you land on.
+
+use std::f32::consts::{PI, TAU};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+use bevy_rapier2d::prelude::*;
+
+/// Heights sampled around the circumference.
+pub const TERRAIN_SAMPLES: usize = 256;
+
+/// Lobes around the circumference of the coarsest noise octave; each further
+/// octave doubles it and halves the amplitude.
+const BASE_FREQUENCY: u32 = 6;
+const OCTAVES: u32 = 4;
+
+#[derive(Component, Debug, Clone)]
+pub struct Terrain {
+    /// Surface radius at each of `TERRAIN_SAMPLES` evenly spaced angles,
+    /// starting along +x and going counter-clockwise.
+    pub heights: Vec<f32>,
+}
+
+/// A stable hash of `n` under `seed` into [0, 1].
+fn hash(seed: u32, n: u32) -> f32 {
+    let mut x = seed.wrapping_mul(0x9E37_79B9) ^ n.wrapping_mul(0x85EB_CA6B);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7FEB_352D);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846C_A68B);
+    x ^= x >> 16;
+    x as f32 / u32::MAX as f32
+}
+
+/// A seed from a planet's name, so the same planet always gets the same terrain.
+pub fn name_seed(name: &str) -> u32 {
+    name.bytes().fold(2166136261u32, |h, b| (h ^ b as u32).wrapping_mul(16777619))
+}
+
+/// Periodic value noise around the circle, in [0, 1], `turns` of the way round.
+fn circular_noise(seed: u32, turns: f32) -> f32 {
+    let mut total = 0.0;
+    let mut weight = 0.0;
+    for octave in 0..OCTAVES {
+        let frequency = BASE_FREQUENCY << octave;
+        let amplitude = 0.5f32.powi(octave as i32);
+
+        let x = turns * frequency as f32;
+        let i = x.floor() as u32;
+        let t = x - x.floor();
+        let a = hash(seed ^ octave, i % frequency);
+        let b = hash(seed ^ octave, (i + 1) % frequency);
+        let smooth = (1.0 - (t * PI).cos()) * 0.5;
+
+        total += (a + (b - a) * smooth) * amplitude;
+        weight += amplitude;
+    }
+    total / weight
+}
+
+impl Terrain {
+    /// Heights within `roughness * radius` of `radius`; zero roughness is a circle.
+    pub fn generate(radius: f32, roughness: f32, seed: u32) -> Terrain {
+        let heights = (0..TERRAIN_SAMPLES).map(|i| {
+            let turns = i as f32 / TERRAIN_SAMPLES as f32;
+            let noise = circular_noise(seed, turns) * 2.0 - 1.0;
+            radius * (1.0 + roughness * noise)
+        }).collect();
+
+        Terrain { heights: heights }
+    }
+
+    /// The surface outline relative to the planet's centre.
+    pub fn outline(&self) -> Vec<Vec2> {
+        let step = TAU / self.heights.len() as f32;
+        self.heights.iter().enumerate()
+            .map(|(i, height)| Vec2::from_angle(i as f32 * step) * *height)
+            .collect()
+    }
+
+    /// Surface radius at `angle` radians from +x, interpolated between samples.
+    pub fn height_at(&self, angle: f32) -> f32 {
+        let count = self.heights.len();
+        let x = angle.rem_euclid(TAU) / TAU * count as f32;
+        let i = x.floor() as usize % count;
+        let t = x - x.floor();
+        self.heights[i] + (self.heights[(i + 1) % count] - self.heights[i]) * t
+    }
+
+    /// A solid fan of triangles from the centre, so the planet has mass and
+    /// nothing can tunnel into a hollow outline.
+    pub fn collider(&self) -> Collider {
+        let outline = self.outline();
+        let triangles = outline.iter().zip(outline.iter().cycle().skip(1))
+            .map(|(a, b)| (Vec2::ZERO, 0.0, Collider::triangle(Vec2::ZERO, *a, *b)))
+            .collect();
+        Collider::compound(triangles)
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        let outline = self.outline();
+        let extent = self.heights.iter().copied().fold(0.0, f32::max);
+
+        let mut positions: Vec<[f32; 3]> = vec![[0.0, 0.0, 0.0]];
+        positions.extend(outline.iter().map(|p| [p.x, p.y, 0.0]));
+        let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+        let uvs: Vec<[f32; 2]> = positions.iter()
+            .map(|p| [0.5 + p[0] / extent * 0.5, 0.5 - p[1] / extent * 0.5])
+            .collect();
+
+        let count = outline.len() as u32;
+        let indices = (0..count)
+            .flat_map(|i| [0, i + 1, (i + 1) % count + 1])
+            .collect();
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::physics::divergence::*;
+use crate::physics::gravity::Landed;
+use crate::physics::orbits::Orbit;
+use crate::planets::planet::Planet;
+use crate::ships::autopilot::{Autopilot, AutopilotMode, ManeuverPlan};
+use crate::ships::landing::surface_velocity;
+use crate::ships::propulsion::*;
+use crate::ships::ship::Player;
+use crate::ships::targeting::{Target, TargetInfo};
+use crate::ships::tiles::TileSet;
+use crate::ships::control::ShipControl;
+
+pub const HUD_FONT: &str = "fonts/DejaVuSansMono.ttf";
+
+#[derive(Resource)]
+pub struct HudFont(pub Handle<Font>);
+
+impl FromWorld for HudFont {
+    fn from_world(world: &mut World) -> Self {
+        HudFont(world.resource::<AssetServer>().load(HUD_FONT))
+    }
+}
+
+impl HudFont {
+    pub fn style(&self, size: f32) -> TextStyle {
+        TextStyle {
+            font: self.0.clone(),
+            font_size: size,
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// One line of the flight HUD, each kept up to date by its own system.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudLine {
+    Velocity,
+    Propulsion,
+    Divergence,
+    Target,
+}
+
+impl HudLine {
+    const ALL: [HudLine; 4] = [HudLine::Velocity, HudLine::Propulsion, HudLine::Divergence, HudLine::Target];
+}
+
+pub fn setup_hud(
+    mut commands: Commands,
+    font: Res<HudFont>,
+) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(10.0),
+                top: Val::Px(10.0),
+                ..default()
+            },
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        for line in HudLine::ALL {
+            parent.spawn((line, TextBundle::from_section("", font.style(16.0))));
+        }
+    });
+}
+
+/// Inertial speed, and speed relative to the surface of the body the player
+/// is orbiting or landed on, which includes that body's spin.
+pub fn update_velocity_hud(
+    ships: Query<(&GlobalTransform, &Velocity, Option<&Orbit>, Option<&Landed>), With<Player>>,
+    planets: Query<(&Name, &GlobalTransform, &Velocity), With<Planet>>,
+    mut lines: Query<(&HudLine, &mut Text)>,
+) {
+    let value = match ships.get_single() {
+        Ok((transform, velocity, orbit, landed)) => {
+            let body = landed.map(|l| l.planet).or(orbit.map(|o| o.planet));
+            match body.and_then(|body| planets.get(body).ok()) {
+                Some((name, planet_transform, planet_velocity)) => {
+                    let offset = (transform.translation() - planet_transform.translation()).truncate();
+                    let surface = velocity.linvel - surface_velocity(planet_velocity, offset);
+                    format!("Speed {:.1}  Surface {:.1} ({})", velocity.linvel.length(), surface.length(), name)
+                },
+                None => format!("Speed {:.1}", velocity.linvel.length()),
+            }
+        },
+        Err(_) => String::new(),
+    };
+
+    for (line, mut text) in lines.iter_mut() {
+        if *line == HudLine::Velocity && text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+pub fn update_propulsion_hud(
+    time: Res<Time>,
+    ships: Query<(&TileSet, &ShipControl, &Autopilot, &ManeuverPlan), With<Player>>,
+    mut lines: Query<(&HudLine, &mut Text)>,
+) {
+    let value = match ships.get_single() {
+        Ok((tileset, control, autopilot, plan)) => {
+            let (fuel, capacity) = fuel_totals(tileset);
+            let mut value = format!(
+                "Fuel {:.2}/{:.2}  dv {:.1}  Throttle {:.0}%",
+                fuel, capacity, delta_v(tileset), control.throttle * 100.0,
+            );
+            if autopilot.mode != AutopilotMode::Off {
+                value += &format!("  AP {:?}", autopilot.mode);
+            }
+            if autopilot.burning() {
+                value += "  BURN";
+            } else if let Some(node) = plan.nodes.first() {
+                value += &format!("  Node T-{:.1} dv {:.2}", node.time - time.elapsed_seconds(), node.delta_v());
+            }
+            value
+        },
+        Err(_) => String::new(),
+    };
+
+    for (line, mut text) in lines.iter_mut() {
+        if *line == HudLine::Propulsion && text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The player's orbit error, with its recent position error as a sparkline
+/// scaled to the divergence threshold.
+pub fn update_divergence_hud(
+    threshold: Res<DivergenceThreshold>,
+    ships: Query<&OrbitError, With<Player>>,
+    mut lines: Query<(&HudLine, &mut Text)>,
+) {
+    let value = match ships.get_single() {
+        Ok(error) => {
+            let top = SPARK_LEVELS.len() - 1;
+            let graph: String = error.history.iter().map(|(position, _)| {
+                let level = (position / threshold.position * top as f32).round() as usize;
+                SPARK_LEVELS[level.min(top)]
+            }).collect();
+            format!("Orbit error {:.3} / {:.3}  {}", error.position, error.velocity, graph)
+        },
+        Err(_) => String::new(),
+    };
+
+    for (line, mut text) in lines.iter_mut() {
+        if *line == HudLine::Divergence && text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Distance and relative motion to the player's target, and when it's
+/// closest if that can be worked out from their orbits.
+pub fn update_target_hud(
+    time: Res<Time>,
+    ships: Query<(&Target, &TargetInfo), With<Player>>,
+    names: Query<&Name>,
+    mut lines: Query<(&HudLine, &mut Text)>,
+) {
+    let value = match ships.get_single() {
+        Ok((target, info)) => {
+            let name = names.get(target.entity).map_or("?", |name| name.as_str());
+            let mut value = format!(
+                "Target {}  Dist {:.1}  Rel {:.2}  Closing {:.2}",
+                name, info.distance, info.relative_velocity.length(), info.closing_speed,
+            );
+            if let Some((when, distance)) = info.closest_approach {
+                value += &format!("  CA {:.1} in {:.1}s", distance, when - time.elapsed_seconds());
+            }
+            value
+        },
+        Err(_) => String::new(),
+    };
+
+    for (line, mut text) in lines.iter_mut() {
+        if *line == HudLine::Target && text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
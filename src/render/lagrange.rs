@@ -0,0 +1,246 @@
+//! Lagrange points of every planet that orbits another, marked on the system
+//! map, and a zero-velocity contour for the player's Jacobi constant that J
+//! toggles in either view.
+
+use bevy::{
+    prelude::*,
+    render::view::RenderLayers,
+    window::PrimaryWindow,
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::common::Mass;
+use crate::input::InputBindings;
+use crate::physics::orbits::*;
+use crate::planets::planet::Planet;
+use crate::render::lines::*;
+use crate::render::map::{map_pixel_size, MapCamera, MAP_LAYER};
+use crate::ships::ship::Player;
+
+const MARKER_PIXELS: f32 = 6.0;
+/// The contour covers this many separations either side of the barycentre.
+const CONTOUR_EXTENT: f32 = 1.6;
+const CONTOUR_CELLS: usize = 120;
+
+/// Whether the zero-velocity contour is drawn.
+#[derive(Resource, Default)]
+pub struct JacobiOverlay {
+    pub enabled: bool,
+}
+
+#[derive(Component)]
+pub struct LagrangeMarkers;
+
+#[derive(Component)]
+pub struct JacobiContour;
+
+pub fn setup_lagrange(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+) {
+    commands.spawn((
+        LagrangeMarkers,
+        MaterialMeshBundle {
+            mesh: meshes.add(Mesh::from(LineList::default())),
+            material: materials.add(LineMaterial {
+                color: Color::rgb(0.4, 1.0, 0.8),
+                width: 1.5,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            ..default()
+        },
+        RenderLayers::layer(MAP_LAYER),
+    ));
+
+    commands.spawn((
+        JacobiContour,
+        MaterialMeshBundle {
+            mesh: meshes.add(Mesh::from(LineList::default())),
+            material: materials.add(LineMaterial {
+                color: Color::rgba(1.0, 0.5, 0.3, 0.8),
+                width: 1.5,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, 0.0, 0.5),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+pub fn toggle_jacobi_overlay(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut overlay: ResMut<JacobiOverlay>,
+    mut contours: Query<&mut Visibility, With<JacobiContour>>,
+) {
+    if !bindings.just_pressed(&keys, "toggle_jacobi") {
+        return;
+    }
+    overlay.enabled = !overlay.enabled;
+    for mut visibility in contours.iter_mut() {
+        *visibility = if overlay.enabled { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+type PlanetQuery<'w, 's> = Query<'w, 's, (Entity, &'static GlobalTransform, &'static Velocity, &'static Mass, Option<&'static Orbit>), With<Planet>>;
+
+/// Deepest chain of primaries followed when working out a planet's velocity.
+const MAX_NESTING: u32 = 8;
+
+/// A planet's world velocity, built up from the orbits of it and its
+/// primaries. Rails bodies' `Velocity` is whatever Rapier made of the last
+/// step, so only the outermost body's is used.
+fn planet_velocity(planets: &PlanetQuery, planet: Entity, time: f32, depth: u32) -> Vec3 {
+    let Ok((_, _, velocity, _, orbit)) = planets.get(planet) else {
+        return Vec3::ZERO;
+    };
+    match orbit {
+        Some(orbit) if depth < MAX_NESTING => {
+            planet_velocity(planets, orbit.planet, time, depth + 1) + velocity_at(orbit, time)
+        },
+        _ => velocity.linvel.extend(0.0),
+    }
+}
+
+/// Every planet orbiting another planet, paired with its primary.
+fn planet_pairs(time: &Time, planets: &PlanetQuery) -> Vec<(Entity, Entity, RestrictedThreeBody)> {
+    let now = time.elapsed_seconds();
+    planets.iter().filter_map(|(secondary, transform, _, mass, orbit)| {
+        let orbit = orbit?;
+        let (primary, primary_transform, _, primary_mass, _) = planets.get(orbit.planet).ok()?;
+        let primary_velocity = planet_velocity(planets, primary, now, 0);
+        let velocity = primary_velocity + velocity_at(orbit, now);
+        let pair = RestrictedThreeBody::new(
+            primary_transform.translation(),
+            primary_velocity,
+            primary_mass.value,
+            transform.translation(),
+            velocity,
+            mass.value,
+        );
+        Some((primary, secondary, pair))
+    }).collect()
+}
+
+fn cross(lines: &mut LineList, at: Vec3, size: f32) {
+    lines.lines.push((at - Vec3::new(size, size, 0.0), at + Vec3::new(size, size, 0.0)));
+    lines.lines.push((at - Vec3::new(size, -size, 0.0), at + Vec3::new(size, -size, 0.0)));
+}
+
+pub fn render_lagrange_points(
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<&Projection, With<MapCamera>>,
+    planets: PlanetQuery,
+    targets: Query<&Handle<Mesh>, With<LagrangeMarkers>>,
+) {
+    let (Ok(window), Ok(projection)) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    let Some(pixel) = map_pixel_size(window, projection) else {
+        return;
+    };
+
+    let mut lines = LineList::default();
+    for (_, _, pair) in planet_pairs(&time, &planets) {
+        for point in pair.lagrange_points() {
+            cross(&mut lines, point, pixel * MARKER_PIXELS * 0.5);
+        }
+    }
+
+    for handle in targets.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = Mesh::from(lines.clone());
+        }
+    }
+}
+
+/// Segments where `f` crosses zero over a square grid of `cells` cells of
+/// size `step` from `origin`, by marching squares.
+fn zero_contour(origin: Vec2, step: f32, cells: usize, f: impl Fn(Vec2) -> f32) -> Vec<(Vec3, Vec3)> {
+    let samples = cells + 1;
+    let point = |i: usize, j: usize| origin + Vec2::new(i as f32, j as f32) * step;
+    let values: Vec<f32> = (0..samples * samples)
+        .map(|n| f(point(n % samples, n / samples)))
+        .collect();
+    let value = |i: usize, j: usize| values[j * samples + i];
+
+    let mut segments = Vec::new();
+    for j in 0..cells {
+        for i in 0..cells {
+            // Corners anticlockwise from the bottom left, and each edge's crossing if any
+            let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+            let crossings: Vec<Option<Vec3>> = (0..4).map(|edge| {
+                let (a, b) = (corners[edge], corners[(edge + 1) % 4]);
+                let (va, vb) = (value(a.0, a.1), value(b.0, b.1));
+                if (va > 0.0) == (vb > 0.0) {
+                    return None;
+                }
+                let t = va / (va - vb);
+                Some(point(a.0, a.1).lerp(point(b.0, b.1), t).extend(0.0))
+            }).collect();
+
+            let found: Vec<Vec3> = crossings.iter().flatten().copied().collect();
+            match found.len() {
+                2 => segments.push((found[0], found[1])),
+                4 => {
+                    // A saddle: the centre decides which corners are joined
+                    let centre = f(point(i, j) + Vec2::splat(step * 0.5));
+                    let edge = |n: usize| crossings[n].unwrap();
+                    if (centre > 0.0) == (value(i, j) > 0.0) {
+                        segments.push((edge(0), edge(1)));
+                        segments.push((edge(2), edge(3)));
+                    } else {
+                        segments.push((edge(3), edge(0)));
+                        segments.push((edge(1), edge(2)));
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+    segments
+}
+
+/// Outlines where the player could get to with its current Jacobi constant
+/// around the pair it's flying in: it can't cross the contour without thrust.
+pub fn render_jacobi_contour(
+    time: Res<Time>,
+    overlay: Res<JacobiOverlay>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    planets: PlanetQuery,
+    players: Query<(&GlobalTransform, &Velocity, &Orbit), With<Player>>,
+    targets: Query<&Handle<Mesh>, With<JacobiContour>>,
+) {
+    if !overlay.enabled {
+        return;
+    }
+
+    let pairs = planet_pairs(&time, &planets);
+    let player = players.get_single().ok().and_then(|(transform, velocity, orbit)| {
+        let (_, _, pair) = pairs.iter()
+            .find(|(primary, secondary, _)| orbit.planet == *primary || orbit.planet == *secondary)?;
+        Some((pair, pair.jacobi_constant(transform.translation(), velocity.linvel.extend(0.0))))
+    });
+
+    // Cleared when the player isn't near a pair
+    let mut lines = LineList::default();
+    if let Some((pair, jacobi)) = player {
+        let extent = pair.separation() * CONTOUR_EXTENT;
+        let origin = pair.barycentre().truncate() - Vec2::splat(extent);
+        let step = 2.0 * extent / CONTOUR_CELLS as f32;
+        lines.lines = zero_contour(origin, step, CONTOUR_CELLS, |at| {
+            2.0 * pair.effective_potential(at.extend(0.0)) - jacobi
+        });
+    }
+
+    for handle in targets.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = Mesh::from(lines.clone());
+        }
+    }
+}
@@ -1,74 +1,168 @@
-//! Create a custom material to draw basic lines in 3D
+//! A material for screen-space thick lines: each segment is expanded to a
+//! quad in the vertex shader, so width is in pixels whatever the zoom, with
+//! optional dashes, per-vertex colour and an anti-aliased edge.
 
 use bevy::{
     pbr::{MaterialPipeline, MaterialPipelineKey},
     prelude::*,
     reflect::TypeUuid,
     render::{
-        mesh::{MeshVertexBufferLayout, PrimitiveTopology},
+        mesh::{Indices, MeshVertexAttribute, MeshVertexBufferLayout, PrimitiveTopology},
         render_resource::{
-            AsBindGroup, PolygonMode, RenderPipelineDescriptor, ShaderRef,
-            SpecializedMeshPipelineError,
+            AsBindGroup, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError, VertexFormat,
         },
     },
 };
 
+/// The other end of the segment this vertex belongs to.
+pub const ATTRIBUTE_LINE_OTHER: MeshVertexAttribute =
+    MeshVertexAttribute::new("Line_Other", 716251903, VertexFormat::Float32x3);
 
-#[derive(Default, AsBindGroup, TypeUuid, Debug, Clone)]
+/// (side of the line, -1 or 1; distance along the line; 1 at a segment's
+/// start and -1 at its end)
+pub const ATTRIBUTE_LINE_PARAMS: MeshVertexAttribute =
+    MeshVertexAttribute::new("Line_Params", 716251904, VertexFormat::Float32x3);
+
+
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
 #[uuid = "050ce6ac-080a-4d8c-b6b5-b5bab7560d8f"]
 pub struct LineMaterial {
+    /// Multiplies the per-vertex colours
     #[uniform(0)]
     pub color: Color,
+    /// In pixels
+    #[uniform(0)]
+    pub width: f32,
+    /// Dash and gap lengths in world units; a zero gap draws a solid line
+    #[uniform(0)]
+    pub dash: f32,
+    #[uniform(0)]
+    pub gap: f32,
+}
+
+impl Default for LineMaterial {
+    fn default() -> Self {
+        LineMaterial {
+            color: Color::WHITE,
+            width: 2.0,
+            dash: 0.0,
+            gap: 0.0,
+        }
+    }
 }
 
 impl Material for LineMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/line_material.wgsl".into()
+    }
+
     fn fragment_shader() -> ShaderRef {
         "shaders/line_material.wgsl".into()
     }
 
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+
     fn specialize(
         _pipeline: &MaterialPipeline<Self>,
         descriptor: &mut RenderPipelineDescriptor,
-        _layout: &MeshVertexBufferLayout,
+        layout: &MeshVertexBufferLayout,
         _key: MaterialPipelineKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
-        // This is the important part to tell bevy to render this material as a line between vertices
-        descriptor.primitive.polygon_mode = PolygonMode::Line;
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            ATTRIBUTE_LINE_OTHER.at_shader_location(1),
+            ATTRIBUTE_LINE_PARAMS.at_shader_location(2),
+            Mesh::ATTRIBUTE_COLOR.at_shader_location(3),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        // Which way round a segment's quad winds depends on the view
+        descriptor.primitive.cull_mode = None;
         Ok(())
     }
 }
 
+/// Accumulates segments as quads with the attributes the line shader needs.
+#[derive(Default)]
+struct LineMeshBuilder {
+    positions: Vec<[f32; 3]>,
+    others: Vec<[f32; 3]>,
+    params: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl LineMeshBuilder {
+    fn segment(&mut self, a: Vec3, b: Vec3, color_a: Color, color_b: Color, distance: f32) {
+        let base = self.positions.len() as u32;
+        let length = a.distance(b);
+        let ends = [
+            (a, b, -1.0, distance, 1.0, color_a),
+            (a, b, 1.0, distance, 1.0, color_a),
+            (b, a, 1.0, distance + length, -1.0, color_b),
+            (b, a, -1.0, distance + length, -1.0, color_b),
+        ];
+        for (this, other, side, along, start, color) in ends {
+            self.positions.push(this.to_array());
+            self.others.push(other.to_array());
+            self.params.push([side, along, start]);
+            self.colors.push(color.as_linear_rgba_f32());
+        }
+        self.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    fn build(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(ATTRIBUTE_LINE_OTHER, self.others);
+        mesh.insert_attribute(ATTRIBUTE_LINE_PARAMS, self.params);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+fn color_at(colors: &[Color], i: usize) -> Color {
+    colors.get(i).copied().unwrap_or(Color::WHITE)
+}
+
 /// A list of lines with a start and end position
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct LineList {
     pub lines: Vec<(Vec3, Vec3)>,
+    /// Optional start and end colour for each line; white if missing
+    pub colors: Vec<(Color, Color)>,
 }
 
 impl From<LineList> for Mesh {
     fn from(line: LineList) -> Self {
-        // This tells wgpu that the positions are list of lines
-        // where every pair is a start and end point
-        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
-
-        let vertices: Vec<_> = line.lines.into_iter().flat_map(|(a, b)| [a, b]).collect();
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-        mesh
+        let mut builder = LineMeshBuilder::default();
+        for (i, (a, b)) in line.lines.into_iter().enumerate() {
+            let (color_a, color_b) = line.colors.get(i).copied().unwrap_or((Color::WHITE, Color::WHITE));
+            builder.segment(a, b, color_a, color_b, 0.0);
+        }
+        builder.build()
     }
 }
 
 /// A list of points that will have a line drawn between each consecutive points
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct LineStrip {
     pub points: Vec<Vec3>,
+    /// Optional colour at each point, blended along the segments; white if missing
+    pub colors: Vec<Color>,
 }
 
 impl From<LineStrip> for Mesh {
     fn from(line: LineStrip) -> Self {
-        // This tells wgpu that the positions are a list of points
-        // where a line will be drawn between each consecutive point
-        let mut mesh = Mesh::new(PrimitiveTopology::LineStrip);
-
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, line.points);
-        mesh
+        let mut builder = LineMeshBuilder::default();
+        let mut distance = 0.0;
+        for (i, pair) in line.points.windows(2).enumerate() {
+            builder.segment(pair[0], pair[1], color_at(&line.colors, i), color_at(&line.colors, i + 1), distance);
+            distance += pair[0].distance(pair[1]);
+        }
+        builder.build()
     }
 }
@@ -0,0 +1,352 @@
+//! The system map: a second, zoomable camera that also sees render layer 1,
+//! where every body gets a fixed-size icon and every moon its sphere of
+//! influence, with names as UI labels. M switches between flight and map.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    input::mouse::MouseWheel,
+    render::{camera::ScalingMode, view::RenderLayers},
+    window::PrimaryWindow,
+};
+
+use crate::common::Mass;
+use crate::input::InputBindings;
+use crate::physics::orbits::*;
+use crate::planets::planet::Planet;
+use crate::render::hud::HudFont;
+use crate::render::lines::*;
+use crate::settings::Settings;
+use crate::ships::ship::{Player, Ship};
+use crate::states::GameState;
+
+/// Only the map camera renders this layer.
+pub const MAP_LAYER: u8 = 1;
+
+const ICON_PIXELS: f32 = 10.0;
+const PICK_PIXELS: f32 = 12.0;
+const SOI_POINTS: u32 = 96;
+
+const MIN_SCALE: f32 = 50.0;
+const MAX_SCALE: f32 = 20000.0;
+
+/// The body picked on the map, which the map camera stays centred on.
+#[derive(Resource, Default)]
+pub struct MapSelection {
+    pub body: Option<Entity>,
+}
+
+#[derive(Component)]
+pub struct FlightCamera;
+
+#[derive(Component)]
+pub struct MapCamera;
+
+#[derive(Component)]
+pub struct MapIcon;
+
+#[derive(Component)]
+pub struct SoiCircle {
+    radius: f32,
+}
+
+#[derive(Component)]
+pub struct MapLabel {
+    body: Entity,
+}
+
+pub fn setup_map_camera(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        MapCamera,
+        Camera3dBundle {
+            camera: Camera {
+                is_active: false,
+                order: 1,
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 100.0).looking_at(Vec3::ZERO, Vec3::Y),
+            projection: OrthographicProjection {
+                scale: 1000.0,
+                scaling_mode: ScalingMode::FixedVertical(1.0),
+                ..default()
+            }.into(),
+            ..default()
+        },
+        RenderLayers::from_layers(&[0, MAP_LAYER]),
+    ));
+}
+
+pub fn toggle_view_mode(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    state: Res<State<GameState>>,
+    mut next: ResMut<NextState<GameState>>,
+) {
+    if bindings.just_pressed(&keys, "toggle_map") {
+        match state.0 {
+            GameState::Flight => next.set(GameState::Map),
+            GameState::Map => next.set(GameState::Flight),
+            _ => {},
+        }
+    }
+}
+
+/// Switches cameras on entering flight or the map; pausing and the menus
+/// keep whichever view was showing behind them.
+pub fn apply_view_mode(
+    state: Res<State<GameState>>,
+    mut flight: Query<&mut Camera, (With<FlightCamera>, Without<MapCamera>)>,
+    mut map: Query<&mut Camera, (With<MapCamera>, Without<FlightCamera>)>,
+) {
+    if !state.is_changed() || !state.0.is_running() {
+        return;
+    }
+    for mut camera in flight.iter_mut() {
+        camera.is_active = state.0 == GameState::Flight;
+    }
+    for mut camera in map.iter_mut() {
+        camera.is_active = state.0 == GameState::Map;
+    }
+}
+
+pub fn add_map_icons(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    bodies: Query<(Entity, Option<&Planet>, Option<&Player>), Or<(Added<Planet>, Added<Ship>)>>,
+) {
+    for (entity, planet, player) in bodies.iter() {
+        let color = match (planet, player) {
+            (Some(_), _) => Color::rgb(0.5, 0.7, 1.0),
+            (None, Some(_)) => Color::GREEN,
+            (None, None) => Color::YELLOW,
+        };
+
+        let icon = commands.spawn((
+            MapIcon,
+            PbrBundle {
+                mesh: meshes.add(shape::Circle::new(0.5).into()),
+                material: materials.add(StandardMaterial {
+                    base_color: color,
+                    unlit: true,
+                    ..default()
+                }),
+                transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                ..default()
+            },
+            RenderLayers::layer(MAP_LAYER),
+        )).id();
+        commands.entity(entity).add_child(icon);
+    }
+}
+
+/// World units per screen pixel for the map camera.
+pub fn map_pixel_size(window: &Window, projection: &Projection) -> Option<f32> {
+    match projection {
+        Projection::Orthographic(orthographic) => Some(orthographic.scale / window.height()),
+        _ => None,
+    }
+}
+
+/// Keeps icons the same size on screen whatever the map zoom.
+pub fn scale_map_icons(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<&Projection, With<MapCamera>>,
+    mut icons: Query<&mut Transform, With<MapIcon>>,
+) {
+    let (Ok(window), Ok(projection)) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    let Some(pixel) = map_pixel_size(window, projection) else {
+        return;
+    };
+
+    let scale = Vec3::splat(pixel * ICON_PIXELS);
+    for mut transform in icons.iter_mut() {
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+    }
+}
+
+/// Draws each moon's sphere of influence around it, redrawn when a re-fit
+/// changes its size.
+pub fn update_soi_circles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    masses: Query<&Mass>,
+    planets: Query<(Entity, &Mass, &Orbit, Option<&Children>), (With<Planet>, Changed<Orbit>)>,
+    mut circles: Query<(&mut SoiCircle, &Handle<Mesh>)>,
+) {
+    for (entity, mass, orbit, children) in planets.iter() {
+        let Ok(primary_mass) = masses.get(orbit.planet) else {
+            continue;
+        };
+        let radius = sphere_of_influence(orbit.semimajor, mass.value, primary_mass.value);
+        let existing = children.and_then(|children| {
+            children.iter().find(|&&child| circles.contains(child)).copied()
+        });
+        if let Some(circle) = existing {
+            if circles.get(circle).map_or(false, |(soi, _)| soi.radius == radius) {
+                continue;
+            }
+        }
+
+        let step = TAU / (SOI_POINTS - 1) as f32;
+        let circle = LineStrip {
+            points: (0..SOI_POINTS).map(|i| {
+                let angle = i as f32 * step;
+                Vec3::new(radius * angle.cos(), radius * angle.sin(), 0.0)
+            }).collect(),
+            ..default()
+        };
+
+        match existing.and_then(|child| circles.get_mut(child).ok()) {
+            Some((mut soi, handle)) => {
+                soi.radius = radius;
+                if let Some(mesh) = meshes.get_mut(handle) {
+                    *mesh = Mesh::from(circle);
+                }
+            },
+            None => {
+                let soi = commands.spawn((
+                    SoiCircle { radius: radius },
+                    MaterialMeshBundle {
+                        mesh: meshes.add(Mesh::from(circle)),
+                        material: materials.add(LineMaterial {
+                            color: Color::rgba(0.5, 0.7, 1.0, 0.6),
+                            width: 1.0,
+                            dash: 4.0,
+                            gap: 4.0,
+                        }),
+                        ..default()
+                    },
+                    RenderLayers::layer(MAP_LAYER),
+                )).id();
+                commands.entity(entity).add_child(soi);
+            },
+        }
+    }
+}
+
+/// Names every planet and ship next to its icon while the map is open.
+pub fn update_map_labels(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    selection: Res<MapSelection>,
+    font: Res<HudFont>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MapCamera>>,
+    bodies: Query<(Entity, &Name, &GlobalTransform), Or<(With<Planet>, With<Ship>)>>,
+    mut labels: Query<(Entity, &MapLabel, &mut Style, &mut Text, &mut Visibility)>,
+) {
+    for (label, map_label, _, _, _) in labels.iter() {
+        if bodies.get(map_label.body).is_err() {
+            commands.entity(label).despawn_recursive();
+        }
+    }
+
+    for (entity, name, _) in bodies.iter() {
+        if !labels.iter().any(|(_, label, _, _, _)| label.body == entity) {
+            commands.spawn((
+                MapLabel { body: entity },
+                TextBundle::from_section(name.as_str(), font.style(14.0)).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                }),
+            ));
+        }
+    }
+
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+
+    for (_, label, mut style, mut text, mut visibility) in labels.iter_mut() {
+        let on_screen = bodies.get(label.body).ok().and_then(|(_, _, transform)| {
+            camera.world_to_viewport(camera_transform, transform.translation())
+        });
+
+        let wanted = match (state.0, on_screen) {
+            (GameState::Map, Some(position)) => {
+                // Viewport coordinates start at the bottom left
+                style.position = UiRect {
+                    left: Val::Px(position.x + ICON_PIXELS),
+                    bottom: Val::Px(position.y - 7.0),
+                    ..default()
+                };
+                Visibility::Inherited
+            },
+            _ => Visibility::Hidden,
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+
+        let color = if selection.body == Some(label.body) { Color::YELLOW } else { Color::WHITE };
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+/// Left click on the map selects the nearest body under the cursor.
+pub fn select_on_click(
+    buttons: Res<Input<MouseButton>>,
+    mut selection: ResMut<MapSelection>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MapCamera>>,
+    bodies: Query<(Entity, &Name, &GlobalTransform), Or<(With<Planet>, With<Ship>)>>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+
+    let picked = bodies.iter()
+        .filter_map(|(entity, name, transform)| {
+            let position = camera.world_to_viewport(camera_transform, transform.translation())?;
+            Some((entity, name, position.distance(cursor)))
+        })
+        .filter(|(_, _, distance)| *distance < PICK_PIXELS)
+        .min_by(|a, b| a.2.total_cmp(&b.2));
+
+    if let Some((entity, name, _)) = picked {
+        info!("Selected {}", name);
+        selection.body = Some(entity);
+    }
+}
+
+/// Mouse wheel zooms the map; the map stays centred on the selection.
+pub fn move_map_camera(
+    settings: Res<Settings>,
+    selection: Res<MapSelection>,
+    mut wheel: EventReader<MouseWheel>,
+    bodies: Query<&GlobalTransform, Without<MapCamera>>,
+    mut cameras: Query<(&mut Transform, &mut Projection), With<MapCamera>>,
+) {
+    let Ok((mut transform, mut projection)) = cameras.get_single_mut() else {
+        return;
+    };
+
+    let zoom = settings.controls.zoom(wheel.iter().map(|event| event.y).sum());
+    if zoom != 0.0 {
+        if let Projection::Orthographic(orthographic) = projection.as_mut() {
+            orthographic.scale = (orthographic.scale * 0.9f32.powf(zoom)).clamp(MIN_SCALE, MAX_SCALE);
+        }
+    }
+
+    if let Some(focus) = selection.body.and_then(|body| bodies.get(body).ok()) {
+        let target = focus.translation();
+        transform.translation.x = target.x;
+        transform.translation.y = target.y;
+    }
+}
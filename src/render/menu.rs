@@ -0,0 +1,297 @@
+//! The main menu, pause menu, settings, editor, and mission briefing and
+//! results screens: a title, some text and a column of buttons over the
+//! paused game, one set per `GameState`.
+
+use bevy::{
+    prelude::*,
+    app::AppExit,
+};
+
+use crate::mission::{Mission, MissionEnded, MissionProgress, MissionStatus};
+use crate::render::hud::HudFont;
+use crate::settings::Settings;
+use crate::states::{GameState, MenuReturn, ResumeState};
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.2);
+const HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.35);
+
+/// Values the settings menu steps through, wrapping around.
+const UI_SCALES: [f64; 5] = [0.75, 1.0, 1.25, 1.5, 2.0];
+const CAMERA_ZOOMS: [f32; 5] = [100.0, 150.0, 200.0, 300.0, 400.0];
+const VOLUME_STEPS: u32 = 10;
+
+/// The root of whichever menu is showing, despawned on leaving its state.
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    Fly,
+    Briefing,
+    Launch,
+    Resume,
+    Editor,
+    MainMenu,
+    Settings,
+    Back,
+    Fullscreen,
+    Vsync,
+    UiScale,
+    CameraZoom,
+    Volume,
+    Mute,
+    Quit,
+}
+
+impl MenuAction {
+    fn label(self) -> &'static str {
+        match self {
+            MenuAction::Fly => "Fly",
+            MenuAction::Briefing => "Mission briefing",
+            MenuAction::Launch => "Launch",
+            MenuAction::Resume => "Resume",
+            MenuAction::Editor => "Ship editor",
+            MenuAction::MainMenu => "Main menu",
+            MenuAction::Settings => "Settings",
+            MenuAction::Back => "Back",
+            MenuAction::Fullscreen => "Fullscreen",
+            MenuAction::Vsync => "VSync",
+            MenuAction::UiScale => "UI scale",
+            MenuAction::CameraZoom => "Camera zoom",
+            MenuAction::Volume => "Volume",
+            MenuAction::Mute => "Mute",
+            MenuAction::Quit => "Quit",
+        }
+    }
+}
+
+fn spawn_menu(commands: &mut Commands, font: &HudFont, title: &str, lines: &[String], actions: &[MenuAction]) {
+    commands.spawn((
+        MenuRoot,
+        NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(title, font.style(36.0)).with_style(Style {
+            margin: UiRect::bottom(Val::Px(16.0)),
+            ..default()
+        }));
+        for line in lines {
+            parent.spawn(TextBundle::from_section(line.as_str(), font.style(16.0)));
+        }
+        if !lines.is_empty() {
+            parent.spawn(NodeBundle {
+                style: Style {
+                    size: Size::height(Val::Px(16.0)),
+                    ..default()
+                },
+                ..default()
+            });
+        }
+        for &action in actions {
+            parent.spawn((
+                action,
+                ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(220.0), Val::Px(40.0)),
+                        margin: UiRect::all(Val::Px(4.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                },
+            )).with_children(|button| {
+                button.spawn(TextBundle::from_section(action.label(), font.style(20.0)));
+            });
+        }
+    });
+}
+
+/// With a mission loaded, flying starts from its briefing.
+pub fn setup_main_menu(
+    mut commands: Commands,
+    font: Res<HudFont>,
+    mission: Option<Res<Mission>>,
+) {
+    let fly = if mission.is_some() { MenuAction::Briefing } else { MenuAction::Fly };
+    spawn_menu(&mut commands, &font, "Spark", &[], &[fly, MenuAction::Editor, MenuAction::Settings, MenuAction::Quit]);
+}
+
+pub fn setup_pause_menu(
+    mut commands: Commands,
+    font: Res<HudFont>,
+) {
+    spawn_menu(&mut commands, &font, "Paused", &[], &[MenuAction::Resume, MenuAction::Settings, MenuAction::MainMenu, MenuAction::Quit]);
+}
+
+/// Nothing to edit yet; the state is here for the ship editor to fill in.
+pub fn setup_editor(
+    mut commands: Commands,
+    font: Res<HudFont>,
+) {
+    spawn_menu(&mut commands, &font, "Ship editor", &[], &[MenuAction::MainMenu]);
+}
+
+fn spawn_settings_menu(commands: &mut Commands, font: &HudFont, settings: &Settings) {
+    let on = |value: bool| if value { "on" } else { "off" };
+    let lines = [
+        format!("Fullscreen {}   VSync {}", on(settings.display.fullscreen), on(settings.display.vsync)),
+        format!("UI scale {:.2}   Camera zoom {:.0}", settings.graphics.ui_scale, settings.graphics.camera_zoom),
+        format!("Volume {:.0}%{}", settings.audio.master * 100.0, if settings.audio.muted { ", muted" } else { "" }),
+    ];
+    let actions = [
+        MenuAction::Fullscreen,
+        MenuAction::Vsync,
+        MenuAction::UiScale,
+        MenuAction::CameraZoom,
+        MenuAction::Volume,
+        MenuAction::Mute,
+        MenuAction::Back,
+    ];
+    spawn_menu(commands, font, "Settings", &lines, &actions);
+}
+
+pub fn setup_settings_menu(
+    mut commands: Commands,
+    font: Res<HudFont>,
+    settings: Res<Settings>,
+) {
+    spawn_settings_menu(&mut commands, &font, &settings);
+}
+
+/// Rebuilds the settings menu to show changed values.
+pub fn refresh_settings_menu(
+    mut commands: Commands,
+    font: Res<HudFont>,
+    settings: Res<Settings>,
+    menus: Query<Entity, With<MenuRoot>>,
+) {
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+    spawn_settings_menu(&mut commands, &font, &settings);
+}
+
+/// The next of `values` after `current`, wrapping back to the first.
+fn next_value<T: PartialOrd + Copy>(values: &[T], current: T) -> T {
+    values.iter().copied().find(|&value| value > current).unwrap_or(values[0])
+}
+
+fn change_setting(settings: &mut Settings, action: MenuAction) {
+    match action {
+        MenuAction::Fullscreen => settings.display.fullscreen = !settings.display.fullscreen,
+        MenuAction::Vsync => settings.display.vsync = !settings.display.vsync,
+        MenuAction::UiScale => settings.graphics.ui_scale = next_value(&UI_SCALES, settings.graphics.ui_scale),
+        MenuAction::CameraZoom => settings.graphics.camera_zoom = next_value(&CAMERA_ZOOMS, settings.graphics.camera_zoom),
+        MenuAction::Volume => {
+            let step = (settings.audio.master * VOLUME_STEPS as f32).round() as u32;
+            settings.audio.master = ((step + 1) % (VOLUME_STEPS + 1)) as f32 / VOLUME_STEPS as f32;
+        },
+        MenuAction::Mute => settings.audio.muted = !settings.audio.muted,
+        _ => {},
+    }
+}
+
+/// The mission's briefing, then its objectives and what would fail it.
+pub fn setup_briefing(
+    mut commands: Commands,
+    font: Res<HudFont>,
+    mission: Res<Mission>,
+    progress: Res<MissionProgress>,
+) {
+    let mut lines: Vec<String> = mission.briefing.lines().map(str::to_string).collect();
+    lines.push(String::new());
+    lines.extend(mission.objectives.iter().enumerate().map(|(i, objective)| {
+        let mark = if i < progress.completed { "[x]" } else { "[ ]" };
+        format!("{} {}", mark, objective.describe())
+    }));
+    lines.extend(mission.failures.iter().map(|failure| format!("  - {}", failure.describe())));
+
+    let launch = if progress.status == MissionStatus::InProgress { MenuAction::Launch } else { MenuAction::Fly };
+    spawn_menu(&mut commands, &font, &mission.name, &lines, &[launch, MenuAction::MainMenu]);
+}
+
+pub fn setup_results(
+    mut commands: Commands,
+    font: Res<HudFont>,
+    mission: Res<Mission>,
+    progress: Res<MissionProgress>,
+) {
+    let title = match &progress.status {
+        MissionStatus::Succeeded => "Mission complete".to_string(),
+        MissionStatus::Failed(reason) => format!("Mission failed: {}", reason),
+        MissionStatus::InProgress => mission.name.clone(),
+    };
+    let mut lines: Vec<String> = mission.objectives.iter().enumerate().map(|(i, objective)| {
+        let mark = if i < progress.completed { "[x]" } else { "[ ]" };
+        format!("{} {}", mark, objective.describe())
+    }).collect();
+    lines.push(format!("Mission time {:.1}s", progress.elapsed));
+
+    spawn_menu(&mut commands, &font, &title, &lines, &[MenuAction::Fly, MenuAction::MainMenu, MenuAction::Quit]);
+}
+
+/// Shows the results as soon as the mission ends.
+pub fn show_results(
+    mut ended: EventReader<MissionEnded>,
+    mut next: ResMut<NextState<GameState>>,
+) {
+    if ended.iter().last().is_some() {
+        next.set(GameState::Results);
+    }
+}
+
+pub fn despawn_menus(
+    mut commands: Commands,
+    menus: Query<Entity, With<MenuRoot>>,
+) {
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+pub fn menu_buttons(
+    state: Res<State<GameState>>,
+    resume: Res<ResumeState>,
+    mut menu: ResMut<MenuReturn>,
+    mut settings: ResMut<Settings>,
+    mut next: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+    mut buttons: Query<(&Interaction, &MenuAction, &mut BackgroundColor), Changed<Interaction>>,
+) {
+    for (interaction, action, mut color) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => match action {
+                MenuAction::Fly | MenuAction::Launch => next.set(GameState::Flight),
+                MenuAction::Briefing => next.set(GameState::Briefing),
+                MenuAction::Resume => next.set(resume.0),
+                MenuAction::Editor => next.set(GameState::Editor),
+                MenuAction::MainMenu => next.set(GameState::MainMenu),
+                MenuAction::Settings => {
+                    menu.0 = state.0;
+                    next.set(GameState::Settings);
+                },
+                MenuAction::Back => next.set(menu.0),
+                MenuAction::Quit => {
+                    info!("Exiting");
+                    exit.send(AppExit);
+                },
+                _ => change_setting(&mut settings, *action),
+            },
+            Interaction::Hovered => *color = HOVER_COLOR.into(),
+            Interaction::None => *color = BUTTON_COLOR.into(),
+        }
+    }
+}
@@ -1 +1,114 @@
+use bevy::{
+    prelude::*,
+    time::common_conditions::on_timer,
+    utils::Duration,
+};
+
+use crate::common::SparkSet;
+use crate::mission::Mission;
+use crate::planets;
+use crate::settings::Settings;
+use crate::states::GameState;
+
+pub mod hud;
+pub mod lagrange;
 pub mod lines;
+pub mod map;
+pub mod menu;
+pub mod orbits;
+pub mod overlay;
+pub mod sprites;
+pub mod target;
+
+/// Tile sprites, planet meshes, orbit paths, the flight HUD, the debug
+/// overlay, the system map, Lagrange points, target markers and the menus.
+/// Needs `DefaultPlugins`, `SparkInputPlugin`, `SparkStatesPlugin`,
+/// `SparkMissionPlugin` and `SparkSettingsPlugin`.
+pub struct SparkRenderPlugin;
+
+impl Plugin for SparkRenderPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .add_plugin(MaterialPlugin::<lines::LineMaterial>::default())
+            .init_resource::<orbits::OrbitFrame>()
+            .init_resource::<hud::HudFont>()
+            .init_resource::<sprites::TileAtlas>()
+            .init_resource::<overlay::DebugOverlay>()
+            .init_resource::<map::MapSelection>()
+            .init_resource::<lagrange::JacobiOverlay>()
+            .add_startup_system(hud::setup_hud)
+            .add_startup_system(overlay::setup_overlay)
+            .add_startup_system(map::setup_map_camera)
+            .add_startup_system(lagrange::setup_lagrange)
+            .add_startup_system(target::setup_target_markers)
+            .add_systems((
+                sprites::render_ship_sprites,
+                planets::planet::render_planets_system,
+                orbits::cleanup_orbit_visuals,
+                orbits::render_orbits.after(orbits::cleanup_orbit_visuals),
+                orbits::update_orbit_positions,
+                orbits::cycle_orbit_frame,
+                orbits::update_orbit_path_visibility.after(orbits::cycle_orbit_frame),
+                hud::update_propulsion_hud,
+                hud::update_divergence_hud,
+                hud::update_velocity_hud,
+                overlay::add_trails,
+                overlay::record_trails,
+                overlay::toggle_overlay,
+                overlay::render_overlay.after(overlay::record_trails).after(overlay::toggle_overlay),
+            ).in_set(SparkSet::Render))
+            .add_system(orbits::keep_paths_inertial
+                        .in_set(SparkSet::Render)
+                        .after(orbits::render_orbits))
+            .add_systems((
+                map::toggle_view_mode,
+                map::apply_view_mode.after(map::toggle_view_mode),
+                map::add_map_icons,
+                map::scale_map_icons.after(map::move_map_camera),
+                map::update_soi_circles,
+                map::select_on_click.run_if(in_state(GameState::Map)),
+                map::move_map_camera.after(map::select_on_click).run_if(in_state(GameState::Map)),
+                map::update_map_labels.after(map::move_map_camera),
+            ).in_set(SparkSet::Render))
+            .add_systems((
+                lagrange::render_lagrange_points.after(map::move_map_camera),
+                lagrange::toggle_jacobi_overlay,
+                lagrange::render_jacobi_contour.after(lagrange::toggle_jacobi_overlay),
+                target::render_target_markers.after(map::move_map_camera),
+                sprites::add_projectile_sprites,
+                hud::update_target_hud,
+            ).in_set(SparkSet::Render))
+            .add_system(menu::setup_main_menu.in_schedule(OnEnter(GameState::MainMenu)))
+            .add_system(menu::despawn_menus.in_schedule(OnExit(GameState::MainMenu)))
+            .add_system(menu::setup_pause_menu.in_schedule(OnEnter(GameState::Paused)))
+            .add_system(menu::despawn_menus.in_schedule(OnExit(GameState::Paused)))
+            .add_system(menu::setup_editor.in_schedule(OnEnter(GameState::Editor)))
+            .add_system(menu::despawn_menus.in_schedule(OnExit(GameState::Editor)))
+            .add_system(menu::setup_briefing
+                        .in_schedule(OnEnter(GameState::Briefing))
+                        .run_if(resource_exists::<Mission>()))
+            .add_system(menu::despawn_menus.in_schedule(OnExit(GameState::Briefing)))
+            .add_system(menu::setup_results
+                        .in_schedule(OnEnter(GameState::Results))
+                        .run_if(resource_exists::<Mission>()))
+            .add_system(menu::despawn_menus.in_schedule(OnExit(GameState::Results)))
+            .add_system(menu::setup_settings_menu.in_schedule(OnEnter(GameState::Settings)))
+            .add_system(menu::despawn_menus.in_schedule(OnExit(GameState::Settings)))
+            .add_system(menu::refresh_settings_menu
+                        .after(menu::menu_buttons)
+                        .run_if(in_state(GameState::Settings))
+                        .run_if(resource_changed::<Settings>()))
+            .add_system(menu::menu_buttons)
+            .add_system(menu::show_results)
+            .add_system(orbits::fade_orbit_paths
+                        .in_set(SparkSet::Render)
+                        .after(orbits::render_orbits)
+                        .run_if(on_timer(Duration::from_secs_f32(0.1))))
+            .add_system(orbits::render_frame_paths
+                        .in_set(SparkSet::Render)
+                        .after(orbits::cleanup_orbit_visuals)
+                        .run_if(on_timer(Duration::from_secs_f32(0.25))));
+    }
+}
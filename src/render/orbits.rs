@@ -0,0 +1,306 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    utils::Duration,
+};
+
+use crate::input::InputBindings;
+use crate::physics::orbits::*;
+use crate::planets::planet::Planet;
+use crate::render::lines::*;
+
+const ORBIT_POINTS: u32 = 128;
+
+/// The fitted orbit of `parent`, drawn as a child of its primary so it moves
+/// with that body.
+#[derive(Component)]
+pub struct OrbitPath {
+    parent: Entity,
+    fitted_at: Duration,
+}
+
+#[derive(Component)]
+pub struct OrbitMarker {
+    parent: Entity
+}
+
+/// The trajectory of `parent` as seen from the body selected in `OrbitFrame`.
+#[derive(Component)]
+pub struct FramePath {
+    parent: Entity
+}
+
+/// Marks orbit and frame paths, which follow their body around but not its
+/// spin.
+#[derive(Component)]
+pub struct InertialPath;
+
+/// The body whose frame trajectories are drawn in; `None` draws every orbit
+/// around its own primary.
+#[derive(Resource, Default)]
+pub struct OrbitFrame {
+    pub body: Option<Entity>
+}
+
+pub fn render_orbits(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut marker_materials: ResMut<Assets<StandardMaterial>>,
+    orbits: Query<(Entity, &Orbit), Changed<Orbit>>,
+    paths: Query<(Entity, &OrbitPath)>,
+    markers: Query<&OrbitMarker>,
+) {
+    for (entity, orbit) in orbits.iter() {
+        let existing = paths.iter().find(|(_, path)| path.parent == entity);
+        if let Some((path_entity, path)) = existing {
+            // Focus updates also count as changes; only redraw on a re-fit
+            if path.fitted_at == orbit.initial_time {
+                continue;
+            }
+            commands.entity(path_entity).despawn_recursive();
+        }
+
+        let c = orbit.eccentricity * orbit.semimajor;
+        let periapsis = orbit.semimajor - c;
+        let apoapsis = orbit.semimajor * 2.0 - periapsis;
+
+        info!("Orbit: {:?}, {:?}, {:?}", periapsis, apoapsis, orbit.argument.to_degrees());
+
+        let points = orbit_to_points(orbit, ORBIT_POINTS);
+
+        let path = commands.spawn((
+            OrbitPath{ parent: entity, fitted_at: orbit.initial_time },
+            InertialPath,
+            MaterialMeshBundle {
+                mesh: meshes.add(Mesh::from(LineStrip {
+                    points: points,
+                    ..default()
+                })),
+                material: materials.add(LineMaterial { color: Color::GREEN, ..default() }),
+                ..default()
+            })).id();
+
+        commands.entity(orbit.planet).add_child(path);
+
+        if !markers.iter().any(|marker| marker.parent == entity) {
+            commands.spawn((
+                OrbitMarker{ parent: entity },
+                MaterialMeshBundle {
+                    mesh: meshes.add(shape::Circle::new(0.5).into()).into(),
+                    material: marker_materials.add(StandardMaterial {
+                        base_color: Color::RED,
+                        unlit: true,
+                        ..default()
+                    }),
+                    transform: Transform::from_translation(Vec3::new(0., 0., 0.)),
+                    ..default()
+                }
+            ));
+        }
+    }
+}
+
+
+/// Re-colours each orbit path so it fades out behind its ship.
+pub fn fade_orbit_paths(
+    mut meshes: ResMut<Assets<Mesh>>,
+    orbits: Query<(&Orbit, &GlobalTransform)>,
+    paths: Query<(&OrbitPath, &Handle<Mesh>)>,
+) {
+    for (path, handle) in paths.iter() {
+        let Ok((orbit, transform)) = orbits.get(path.parent) else {
+            continue;
+        };
+        let Some(mesh) = meshes.get_mut(handle) else {
+            continue;
+        };
+
+        // The ship's angle from periapsis, measured the way `orbit_to_points` steps
+        let offset = transform.translation() - orbit.focus;
+        let ship_angle = offset.y.atan2(offset.x) - orbit.argument;
+        let step = TAU / (ORBIT_POINTS - 1) as f32;
+
+        let colors = (0..ORBIT_POINTS).map(|i| {
+            let angle = i as f32 * step;
+            let ahead = if orbit.clockwise { ship_angle - angle } else { angle - ship_angle };
+            let fade = 1.0 - 0.85 * ahead.rem_euclid(TAU) / TAU;
+            Color::rgba(1.0, 1.0, 1.0, fade)
+        }).collect();
+
+        *mesh = Mesh::from(LineStrip {
+            points: orbit_to_points(orbit, ORBIT_POINTS),
+            colors: colors,
+        });
+    }
+}
+
+
+pub fn update_orbit_positions(
+    time: Res<Time>,
+    orbits: Query<&Orbit>,
+    mut markers: Query<(&OrbitMarker, &mut Transform)>
+) {
+    for (marker, mut transform) in markers.iter_mut() {
+        if let Ok(orbit) = orbits.get(marker.parent) {
+            transform.translation = world_position_at(orbit, time.elapsed_seconds());
+        }
+    }
+}
+
+
+pub fn cleanup_orbit_visuals(
+    mut commands: Commands,
+    orbits: Query<&Orbit>,
+    paths: Query<(Entity, &OrbitPath)>,
+    markers: Query<(Entity, &OrbitMarker)>,
+    frame_paths: Query<(Entity, &FramePath)>,
+) {
+    let parents = paths.iter().map(|(e, p)| (e, p.parent))
+        .chain(markers.iter().map(|(e, m)| (e, m.parent)))
+        .chain(frame_paths.iter().map(|(e, f)| (e, f.parent)));
+
+    for (entity, parent) in parents {
+        if orbits.get(parent).is_err() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+
+pub fn cycle_orbit_frame(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut frame: ResMut<OrbitFrame>,
+    planets: Query<(Entity, &Name), With<Planet>>,
+) {
+    if !bindings.just_pressed(&keys, "cycle_orbit_frame") {
+        return;
+    }
+
+    let mut bodies: Vec<(Entity, &Name)> = planets.iter().collect();
+    bodies.sort_by_key(|(entity, _)| *entity);
+
+    let next = match frame.body {
+        None => bodies.first(),
+        Some(current) => bodies.iter()
+            .skip_while(|(entity, _)| *entity != current)
+            .nth(1),
+    };
+
+    frame.body = next.map(|(entity, _)| *entity);
+    match next {
+        Some((_, name)) => info!("Orbit frame: {}", name),
+        None => info!("Orbit frame: primaries"),
+    }
+}
+
+
+pub fn update_orbit_path_visibility(
+    frame: Res<OrbitFrame>,
+    orbits: Query<&Orbit>,
+    mut paths: Query<(&OrbitPath, &mut Visibility)>,
+) {
+    for (path, mut visibility) in paths.iter_mut() {
+        let shown = match (frame.body, orbits.get(path.parent)) {
+            (None, _) => true,
+            (Some(body), Ok(orbit)) => orbit.planet == body && path.parent != body,
+            (Some(_), Err(_)) => false,
+        };
+        let wanted = if shown { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
+
+
+/// Orbit and frame paths hang off a body so they move with it, but must not
+/// spin with it.
+pub fn keep_paths_inertial(
+    bodies: Query<&Transform, Without<InertialPath>>,
+    mut paths: Query<(&Parent, &mut Transform), With<InertialPath>>,
+) {
+    for (parent, mut transform) in paths.iter_mut() {
+        if let Ok(body) = bodies.get(parent.get()) {
+            let rotation = body.rotation.inverse();
+            if transform.rotation != rotation {
+                transform.rotation = rotation;
+            }
+        }
+    }
+}
+
+
+/// Redraws every orbit not already around the frame body as its path
+/// relative to that body over one of its own periods. Nested primaries are
+/// treated as fixed over the window.
+pub fn render_frame_paths(
+    mut commands: Commands,
+    time: Res<Time>,
+    frame: Res<OrbitFrame>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    orbits: Query<(Entity, &Orbit)>,
+    frame_bodies: Query<(&GlobalTransform, Option<&Orbit>)>,
+    paths: Query<(Entity, &FramePath, &Parent, &Handle<Mesh>)>,
+) {
+    let Some((body, (body_transform, body_orbit))) = frame.body
+        .and_then(|body| frame_bodies.get(body).ok().map(|found| (body, found))) else {
+        for (path, _, _, _) in paths.iter() {
+            commands.entity(path).despawn_recursive();
+        }
+        return;
+    };
+
+    let now = time.elapsed_seconds();
+    let body_position = |t: f32| body_orbit.map_or(body_transform.translation(), |o| world_position_at(o, t));
+
+    for (path, frame_path, parent, _) in paths.iter() {
+        let stale = parent.get() != body || orbits.get(frame_path.parent)
+            .map_or(true, |(_, orbit)| orbit.planet == body);
+        if stale {
+            commands.entity(path).despawn_recursive();
+        }
+    }
+
+    for (entity, orbit) in orbits.iter() {
+        if entity == body || orbit.planet == body || !orbit.period.is_finite() {
+            continue;
+        }
+
+        let step = orbit.period / (ORBIT_POINTS - 1) as f32;
+        let points: Vec<Vec3> = (0..ORBIT_POINTS).map(|i| {
+            let t = now + i as f32 * step;
+            world_position_at(orbit, t) - body_position(t)
+        }).collect();
+
+        let existing = paths.iter()
+            .find(|(_, frame_path, parent, _)| frame_path.parent == entity && parent.get() == body);
+
+        match existing {
+            Some((_, _, _, mesh)) => {
+                if let Some(mesh) = meshes.get_mut(mesh) {
+                    *mesh = Mesh::from(LineStrip { points: points, ..default() });
+                }
+            },
+            None => {
+                let path = commands.spawn((
+                    FramePath { parent: entity },
+                    InertialPath,
+                    MaterialMeshBundle {
+                        mesh: meshes.add(Mesh::from(LineStrip { points: points, ..default() })),
+                        material: materials.add(LineMaterial {
+                            color: Color::CYAN,
+                            width: 1.5,
+                            dash: 2.0,
+                            gap: 1.5,
+                        }),
+                        ..default()
+                    })).id();
+                commands.entity(body).add_child(path);
+            },
+        }
+    }
+}
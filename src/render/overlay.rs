@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::input::InputBindings;
+use crate::physics::gravity::{Orbital, OnRails};
+use crate::ships::propulsion::AppliedThrust;
+use crate::render::lines::*;
+
+const TRAIL_INTERVAL: f32 = 0.1;
+const TRAIL_LENGTH: usize = 200;
+
+/// Arrow lengths per unit of velocity and of acceleration.
+const VELOCITY_SCALE: f32 = 0.5;
+const ACCELERATION_SCALE: f32 = 0.25;
+
+/// Trails, velocity and force arrows for every orbital. F3 toggles it.
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+/// Recent world positions, oldest first.
+#[derive(Component, Default)]
+pub struct Trail {
+    pub points: VecDeque<Vec3>,
+    since_sample: f32,
+}
+
+#[derive(Component)]
+pub struct OverlayLines;
+
+pub fn setup_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+) {
+    commands.spawn((
+        OverlayLines,
+        MaterialMeshBundle {
+            mesh: meshes.add(Mesh::from(LineList::default())),
+            material: materials.add(LineMaterial { width: 1.5, ..default() }),
+            transform: Transform::from_xyz(0.0, 0.0, 0.5),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+pub fn toggle_overlay(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut lines: Query<&mut Visibility, With<OverlayLines>>,
+) {
+    if !bindings.just_pressed(&keys, "toggle_overlay") {
+        return;
+    }
+    overlay.enabled = !overlay.enabled;
+    for mut visibility in lines.iter_mut() {
+        *visibility = if overlay.enabled { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+pub fn add_trails(
+    mut commands: Commands,
+    orbitals: Query<Entity, (Added<Orbital>, Without<OnRails>)>,
+) {
+    for entity in orbitals.iter() {
+        commands.entity(entity).insert(Trail::default());
+    }
+}
+
+/// Trails keep recording while the overlay is hidden, so they're full when shown.
+pub fn record_trails(
+    time: Res<Time>,
+    mut trails: Query<(&GlobalTransform, &mut Trail)>,
+) {
+    for (transform, mut trail) in trails.iter_mut() {
+        trail.since_sample += time.delta_seconds();
+        if trail.since_sample < TRAIL_INTERVAL {
+            continue;
+        }
+        trail.since_sample = 0.0;
+        trail.points.push_back(transform.translation());
+        if trail.points.len() > TRAIL_LENGTH {
+            trail.points.pop_front();
+        }
+    }
+}
+
+fn arrow(lines: &mut LineList, from: Vec3, vector: Vec3, color: Color) {
+    let length = vector.length();
+    if length < 0.01 {
+        return;
+    }
+    let tip = from + vector;
+    let back = -vector / length * length.min(2.0) * 0.4;
+    let side = Vec3::new(-back.y, back.x, 0.0) * 0.5;
+
+    for (a, b) in [(from, tip), (tip, tip + back + side), (tip, tip + back - side)] {
+        lines.lines.push((a, b));
+        lines.colors.push((color, color));
+    }
+}
+
+pub fn render_overlay(
+    overlay: Res<DebugOverlay>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    targets: Query<&Handle<Mesh>, With<OverlayLines>>,
+    ships: Query<(&GlobalTransform, &Trail, &Velocity, Option<&ExternalForce>, Option<&AppliedThrust>, Option<&ReadMassProperties>)>,
+) {
+    if !overlay.enabled {
+        return;
+    }
+
+    let mut lines = LineList::default();
+
+    for (transform, trail, velocity, force, thrust, mass_props) in ships.iter() {
+        let count = trail.points.len();
+        let age = |n: usize| Color::rgba(0.6, 0.8, 1.0, n as f32 / count as f32);
+        for (i, pair) in trail.points.iter().zip(trail.points.iter().skip(1)).enumerate() {
+            lines.lines.push((*pair.0, *pair.1));
+            lines.colors.push((age(i), age(i + 1)));
+        }
+
+        let position = transform.translation();
+        arrow(&mut lines, position, velocity.linvel.extend(0.0) * VELOCITY_SCALE, Color::YELLOW);
+
+        let mass = mass_props.map_or(0.0, |m| m.0.mass);
+        if mass <= 0.0 {
+            continue;
+        }
+        let thrust = thrust.map_or(Vec2::ZERO, |t| t.force);
+        let gravity = force.map_or(Vec2::ZERO, |f| f.force) - thrust;
+        arrow(&mut lines, position, (gravity / mass).extend(0.0) * ACCELERATION_SCALE, Color::FUCHSIA);
+        arrow(&mut lines, position, (thrust / mass).extend(0.0) * ACCELERATION_SCALE, Color::ORANGE);
+    }
+
+    for handle in targets.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = Mesh::from(lines.clone());
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use crate::ships::tiles::*;
+use crate::ships::weapons::Projectile;
+
+pub const TILE_ATLAS: &str = "sprites/tiles.png";
+
+/// The atlas is one column per tile kind, drawn facing `Up`, with the
+/// intact sprites in the top row and the damaged ("B") ones below.
+const ATLAS_COLUMNS: u32 = 7;
+const ATLAS_ROWS: u32 = 2;
+
+pub fn atlas_frame(kind: TileKind, damaged: bool) -> (u32, u32) {
+    let column = match kind {
+        TileKind::Hull => 0,
+        TileKind::DockingPort => 1,
+        TileKind::FuelTank => 2,
+        TileKind::Engine => 3,
+        TileKind::Rcs => 4,
+        TileKind::Gun => 5,
+        TileKind::MissileLauncher => 6,
+    };
+    (column, if damaged { 1 } else { 0 })
+}
+
+#[derive(Resource)]
+pub struct TileAtlas {
+    pub material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for TileAtlas {
+    fn from_world(world: &mut World) -> Self {
+        let image = world.resource::<AssetServer>().load(TILE_ATLAS);
+
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let material = materials.add(StandardMaterial {
+            base_color_texture: Some(image),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+
+        TileAtlas { material }
+    }
+}
+
+/// All of a ship's tile sprites, drawn as one mesh.
+#[derive(Component)]
+pub struct ShipSprite;
+
+/// One textured quad per tile, rotated to the tile's facing.
+pub fn tileset_mesh(tileset: &TileSet) -> Mesh {
+    let count = tileset.tiles.len();
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(count * 4);
+    let mut normals: Vec<[f32; 3]> = Vec::with_capacity(count * 4);
+    let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(count * 4);
+    let mut indices: Vec<u32> = Vec::with_capacity(count * 6);
+
+    let corners = [
+        Vec2::new(-0.5, -0.5),
+        Vec2::new(0.5, -0.5),
+        Vec2::new(0.5, 0.5),
+        Vec2::new(-0.5, 0.5),
+    ];
+
+    for tile in tileset.tiles.values() {
+        let (column, row) = atlas_frame(tile.kind, tile.damaged);
+        let u0 = column as f32 / ATLAS_COLUMNS as f32;
+        let u1 = (column + 1) as f32 / ATLAS_COLUMNS as f32;
+        let v0 = row as f32 / ATLAS_ROWS as f32;
+        let v1 = (row + 1) as f32 / ATLAS_ROWS as f32;
+        let frame = [[u0, v1], [u1, v1], [u1, v0], [u0, v0]];
+
+        let centre = Vec2::new(tile.pos.0 as f32, tile.pos.1 as f32);
+        let rotation = Vec2::from_angle(tile.facing.quarter_turns() as f32 * std::f32::consts::FRAC_PI_2);
+
+        let base = positions.len() as u32;
+        for (corner, uv) in corners.iter().zip(frame) {
+            let p = centre + rotation.rotate(*corner);
+            positions.push([p.x, p.y, 0.0]);
+            normals.push([0.0, 0.0, 1.0]);
+            uvs.push(uv);
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+pub fn render_ship_sprites(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    atlas: Res<TileAtlas>,
+    ships: Query<(Entity, &TileSet, Option<&Children>), Changed<TileSet>>,
+    sprites: Query<&Handle<Mesh>, With<ShipSprite>>,
+) {
+    for (ship, tileset, children) in ships.iter() {
+        let existing = children.and_then(|children| {
+            children.iter().find_map(|&child| sprites.get(child).ok())
+        });
+
+        match existing.and_then(|handle| meshes.get_mut(handle)) {
+            Some(mesh) => *mesh = tileset_mesh(tileset),
+            None => {
+                let sprite = commands.spawn((
+                    ShipSprite,
+                    PbrBundle {
+                        mesh: meshes.add(tileset_mesh(tileset)),
+                        material: atlas.material.clone(),
+                        ..default()
+                    },
+                )).id();
+                commands.entity(ship).add_child(sprite);
+            },
+        }
+    }
+}
+
+/// A dot for each new projectile, orange for guided ones.
+pub fn add_projectile_sprites(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    projectiles: Query<(Entity, &Projectile), Added<Projectile>>,
+) {
+    for (entity, projectile) in projectiles.iter() {
+        let color = if projectile.stats.thrust > 0.0 { Color::ORANGE } else { Color::rgb(1.0, 1.0, 0.6) };
+        let sprite = commands.spawn(PbrBundle {
+            mesh: meshes.add(shape::Circle::new(0.2).into()),
+            material: materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, 0.0, 0.5),
+            ..default()
+        }).id();
+        commands.entity(entity).add_child(sprite);
+    }
+}
@@ -0,0 +1,114 @@
+//! Target-relative prograde and retrograde markers, and the gun lead
+//! indicator, drawn on a ring around the player's ship.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::render::lines::*;
+use crate::render::map::{map_pixel_size, FlightCamera, MapCamera};
+use crate::ships::ship::Player;
+use crate::ships::targeting::TargetInfo;
+use crate::ships::weapons::LeadSolution;
+
+/// Distance of the markers from the ship, and their size, in pixels.
+const RING_PIXELS: f32 = 60.0;
+const MARKER_PIXELS: f32 = 7.0;
+const MARKER_POINTS: usize = 16;
+
+#[derive(Component)]
+pub struct TargetMarkers;
+
+pub fn setup_target_markers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+) {
+    commands.spawn((
+        TargetMarkers,
+        MaterialMeshBundle {
+            mesh: meshes.add(Mesh::from(LineList::default())),
+            material: materials.add(LineMaterial { width: 2.0, ..default() }),
+            transform: Transform::from_xyz(0.0, 0.0, 2.0),
+            ..default()
+        },
+    ));
+}
+
+fn ring(lines: &mut LineList, centre: Vec3, radius: f32, color: Color) {
+    let point = |n: usize| {
+        let angle = n as f32 * TAU / MARKER_POINTS as f32;
+        centre + Vec3::new(angle.cos(), angle.sin(), 0.0) * radius
+    };
+    for i in 0..MARKER_POINTS {
+        lines.lines.push((point(i), point(i + 1)));
+        lines.colors.push((color, color));
+    }
+}
+
+fn diamond(lines: &mut LineList, centre: Vec3, size: f32, color: Color) {
+    let corners = [Vec3::X, Vec3::Y, -Vec3::X, -Vec3::Y].map(|corner| centre + corner * size);
+    for i in 0..4 {
+        lines.lines.push((corners[i], corners[(i + 1) % 4]));
+        lines.colors.push((color, color));
+    }
+}
+
+/// A circle with a dot for prograde, crossed out for retrograde, like a
+/// navball's. A diamond on the ring shows where to point the guns, and a
+/// smaller one where the rounds will meet the target.
+pub fn render_target_markers(
+    mut meshes: ResMut<Assets<Mesh>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &Projection), Or<(With<FlightCamera>, With<MapCamera>)>>,
+    ships: Query<(&GlobalTransform, &TargetInfo, Option<&LeadSolution>), With<Player>>,
+    targets: Query<&Handle<Mesh>, With<TargetMarkers>>,
+) {
+    // Sized for whichever view is showing
+    let projection = cameras.iter().find(|(camera, _)| camera.is_active).map(|(_, projection)| projection);
+    let (Ok(window), Some(projection)) = (windows.get_single(), projection) else {
+        return;
+    };
+    let Some(pixel) = map_pixel_size(window, projection) else {
+        return;
+    };
+
+    let mut lines = LineList::default();
+    if let Ok((transform, info, lead)) = ships.get_single() {
+        let direction = info.relative_velocity.normalize_or_zero().extend(0.0);
+        if direction != Vec3::ZERO {
+            let size = pixel * MARKER_PIXELS;
+            let prograde = transform.translation() + direction * pixel * RING_PIXELS;
+            let retrograde = transform.translation() - direction * pixel * RING_PIXELS;
+            let (green, red) = (Color::rgb(0.4, 1.0, 0.4), Color::rgb(1.0, 0.4, 0.4));
+
+            ring(&mut lines, prograde, size, green);
+            lines.lines.push((prograde - Vec3::X * size * 0.2, prograde + Vec3::X * size * 0.2));
+            lines.colors.push((green, green));
+
+            ring(&mut lines, retrograde, size, red);
+            let diagonal = Vec3::new(size, size, 0.0) * 0.7;
+            let other = Vec3::new(size, -size, 0.0) * 0.7;
+            lines.lines.push((retrograde - diagonal, retrograde + diagonal));
+            lines.lines.push((retrograde - other, retrograde + other));
+            lines.colors.push((red, red));
+            lines.colors.push((red, red));
+        }
+
+        if let Some(lead) = lead {
+            let yellow = Color::YELLOW;
+            let aim = transform.translation() + lead.direction.extend(0.0) * pixel * RING_PIXELS;
+            diamond(&mut lines, aim, pixel * MARKER_PIXELS, yellow);
+            diamond(&mut lines, lead.point, pixel * MARKER_PIXELS * 0.5, yellow);
+        }
+    }
+
+    for handle in targets.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = Mesh::from(lines.clone());
+        }
+    }
+}
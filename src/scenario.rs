@@ -0,0 +1,85 @@
+use std::{
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::physics::gravity::PlaceInOrbit;
+use crate::planets::planet::{spawn_planet, PlanetSpec};
+use crate::ships::ship::{spawn_ship, Player};
+use crate::ships::tiles::{Tile, TileSet};
+use crate::ships::traffic::{TrafficSpawner, TrafficSpec};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ShipSpec {
+    pub name: String,
+    pub tiles: Vec<Tile>,
+    #[serde(default)]
+    pub position: (f32, f32),
+    #[serde(default)]
+    pub velocity: (f32, f32),
+    /// Starts on this orbit instead, ignoring `position` and `velocity`
+    #[serde(default)]
+    pub orbit: Option<PlaceInOrbit>,
+    #[serde(default)]
+    pub player: bool,
+}
+
+#[derive(Resource, Deserialize, Debug, Clone)]
+pub struct Scenario {
+    pub planets: Vec<PlanetSpec>,
+    pub ships: Vec<ShipSpec>,
+    #[serde(default)]
+    pub traffic: Vec<TrafficSpec>,
+}
+
+impl Scenario {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scenario, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let scenario = ron::from_str(&source)?;
+        Ok(scenario)
+    }
+}
+
+pub fn spawn_scenario_system(
+    mut commands: Commands,
+    scenario: Res<Scenario>,
+) {
+    spawn_scenario(&mut commands, &scenario);
+}
+
+pub fn spawn_scenario(
+    commands: &mut Commands,
+    scenario: &Scenario,
+) {
+    for planet in scenario.planets.iter() {
+        spawn_planet(commands, planet);
+    }
+
+    for ship in scenario.ships.iter() {
+        let (x, y) = ship.position;
+        let (vx, vy) = ship.velocity;
+        let entity = spawn_ship(
+            commands,
+            &ship.name,
+            TileSet::from(ship.tiles.clone()),
+            Vec2::new(x, y),
+            Vec2::new(vx, vy),
+        );
+        if let Some(orbit) = &ship.orbit {
+            commands.entity(entity).insert(orbit.clone());
+        }
+        if ship.player {
+            commands.entity(entity).insert(Player);
+        }
+    }
+
+    for traffic in scenario.traffic.iter() {
+        commands.spawn(TrafficSpawner { spec: traffic.clone() });
+    }
+
+    info!("Spawned scenario: {} planets, {} ships", scenario.planets.len(), scenario.ships.len());
+}
@@ -0,0 +1,212 @@
+//! Player settings. The shipped defaults are in `config/display_config.ron`;
+//! any change is saved to `config/user_settings.ron`, which is loaded instead
+//! from then on. Changes apply as soon as the `Settings` resource changes.
+
+use std::{
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use bevy::{
+    prelude::*,
+    ui::UiScale,
+    window::{PresentMode, PrimaryWindow, WindowMode, WindowResolution},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioSettings;
+use crate::render::map::FlightCamera;
+
+pub const DEFAULT_SETTINGS_PATH: &str = "config/display_config.ron";
+pub const SETTINGS_PATH: &str = "config/user_settings.ron";
+
+/// Where to load settings from: the player's saved settings once there are
+/// any, otherwise the defaults.
+pub fn settings_path() -> &'static str {
+    if Path::new(SETTINGS_PATH).exists() { SETTINGS_PATH } else { DEFAULT_SETTINGS_PATH }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub title: String,
+    /// Window size; the platform's default if unset
+    pub dimensions: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    pub vsync: bool,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        DisplaySettings {
+            title: "Spark".to_string(),
+            dimensions: Some((1440, 900)),
+            fullscreen: false,
+            vsync: true,
+        }
+    }
+}
+
+impl DisplaySettings {
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync }
+    }
+
+    /// The primary window these settings describe.
+    pub fn window(&self) -> Window {
+        let mut window = Window {
+            title: self.title.clone(),
+            mode: self.window_mode(),
+            present_mode: self.present_mode(),
+            ..default()
+        };
+        if let Some((width, height)) = self.dimensions {
+            window.resolution = WindowResolution::new(width as f32, height as f32);
+        }
+        window
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub ui_scale: f64,
+    /// Orthographic scale of the flight camera: world units across the
+    /// window's height
+    pub camera_zoom: f32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            ui_scale: 1.0,
+            camera_zoom: 200.0,
+        }
+    }
+}
+
+/// Mouse settings; key bindings are in `config/input.ron`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ControlSettings {
+    /// Multiplies how far each mouse wheel step zooms the map
+    pub zoom_sensitivity: f32,
+    pub invert_zoom: bool,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        ControlSettings {
+            zoom_sensitivity: 1.0,
+            invert_zoom: false,
+        }
+    }
+}
+
+impl ControlSettings {
+    /// Map zoom steps for `wheel` mouse wheel steps.
+    pub fn zoom(&self, wheel: f32) -> f32 {
+        let zoom = wheel * self.zoom_sensitivity;
+        if self.invert_zoom { -zoom } else { zoom }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub display: DisplaySettings,
+    pub graphics: GraphicsSettings,
+    pub audio: AudioSettings,
+    pub controls: ControlSettings,
+}
+
+impl Settings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Settings, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let settings = ron::from_str(&source)?;
+        Ok(settings)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, source)?;
+        Ok(())
+    }
+}
+
+/// Keeps the window, UI, flight camera and audio in line with `Settings`,
+/// and saves them whenever they change. Needs `DefaultPlugins`.
+pub struct SparkSettingsPlugin;
+
+impl Plugin for SparkSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Settings>()
+            .add_systems((
+                apply_settings,
+                save_settings,
+            ).distributive_run_if(resource_changed::<Settings>()));
+    }
+}
+
+pub fn apply_settings(
+    settings: Res<Settings>,
+    mut ui_scale: ResMut<UiScale>,
+    mut audio: ResMut<AudioSettings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut Projection, With<FlightCamera>>,
+) {
+    if let Ok(mut window) = windows.get_single_mut() {
+        let display = &settings.display;
+        if window.mode != display.window_mode() {
+            window.mode = display.window_mode();
+        }
+        if window.present_mode != display.present_mode() {
+            window.present_mode = display.present_mode();
+        }
+        if window.title != display.title {
+            window.title = display.title.clone();
+        }
+    }
+
+    if ui_scale.scale != settings.graphics.ui_scale {
+        ui_scale.scale = settings.graphics.ui_scale;
+    }
+
+    for mut projection in cameras.iter_mut() {
+        if let Projection::Orthographic(orthographic) = projection.as_mut() {
+            orthographic.scale = settings.graphics.camera_zoom;
+        }
+    }
+
+    *audio = settings.audio.clone();
+}
+
+pub fn save_settings(
+    settings: Res<Settings>,
+) {
+    // Nothing to save until something changes them after loading
+    if settings.is_added() {
+        return;
+    }
+    match settings.save(SETTINGS_PATH) {
+        Ok(()) => info!("Saved settings to {}", SETTINGS_PATH),
+        Err(err) => warn!("Couldn't save settings to {}: {}", SETTINGS_PATH, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings_load() {
+        let settings = Settings::load(DEFAULT_SETTINGS_PATH).unwrap();
+        assert_eq!(settings.display.title, "Spark");
+    }
+}
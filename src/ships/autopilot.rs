@@ -0,0 +1,274 @@
+//! Flies a ship by writing its `ShipControl`, the same way the keyboard
+//! does, so the player and NPCs share one controller.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::control::ShipControl;
+use super::propulsion::*;
+use super::tiles::TileSet;
+use crate::common::Mass;
+use crate::physics::orbits::*;
+use crate::planets::planet::Planet;
+
+/// How long the attitude controller takes to correct a turn-rate error.
+const ATTITUDE_RESPONSE: f32 = 0.2;
+/// Burns only run with the engines pointed within about 5 degrees.
+const BURN_ALIGNMENT: f32 = 0.996;
+/// Burns finish once this much delta-v is left.
+const BURN_TOLERANCE: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AutopilotMode {
+    #[default]
+    Off,
+    /// Point the engines along, or against, the velocity relative to the primary
+    Prograde,
+    Retrograde,
+    /// Plan a node that circularizes at the next apsis, then fly it
+    CircularizeApoapsis,
+    CircularizePeriapsis,
+    /// Fly each `ManeuverPlan` node in turn, then switch off
+    ExecuteNode,
+}
+
+/// A burn in progress, fixed in world space when it starts since the orbit
+/// is re-fitted under thrust.
+#[derive(Debug, Clone, Copy)]
+struct Burn {
+    direction: Vec2,
+    remaining: Vec2,
+}
+
+impl Burn {
+    fn done(&self) -> bool {
+        self.remaining.length() < BURN_TOLERANCE || self.remaining.dot(self.direction) <= 0.0
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Autopilot {
+    pub mode: AutopilotMode,
+    burn: Option<Burn>,
+}
+
+impl Autopilot {
+    pub fn engage(&mut self, mode: AutopilotMode) {
+        self.mode = mode;
+        self.burn = None;
+    }
+
+    pub fn burning(&self) -> bool {
+        self.burn.is_some()
+    }
+}
+
+/// A planned burn at `time` (seconds on the same clock as `Orbit::initial_time`),
+/// in the orbit's frame at that moment.
+#[derive(Debug, Clone, Copy)]
+pub struct ManeuverNode {
+    pub time: f32,
+    pub prograde: f32,
+    /// Away from the primary
+    pub radial: f32,
+}
+
+impl ManeuverNode {
+    pub fn delta_v(&self) -> f32 {
+        Vec2::new(self.prograde, self.radial).length()
+    }
+
+    /// The burn as a world-space delta-v, given the orbit it starts from.
+    pub fn world_delta_v(&self, orbit: &Orbit) -> Vec2 {
+        let prograde = velocity_at(orbit, self.time).truncate().normalize_or_zero();
+        let radial = (world_position_at(orbit, self.time) - orbit.focus).truncate().normalize_or_zero();
+        prograde * self.prograde + radial * self.radial
+    }
+
+    /// The node at `time` that `world_delta_v` turns back into `delta_v`.
+    /// Prograde and radial are only perpendicular on circular orbits, so
+    /// this solves for both together.
+    pub fn from_world(orbit: &Orbit, time: f32, delta_v: Vec2) -> ManeuverNode {
+        let prograde = velocity_at(orbit, time).truncate().normalize_or_zero();
+        let radial = (world_position_at(orbit, time) - orbit.focus).truncate().normalize_or_zero();
+        let determinant = prograde.perp_dot(radial);
+        if determinant.abs() < f32::EPSILON {
+            return ManeuverNode { time: time, prograde: delta_v.dot(prograde), radial: 0.0 };
+        }
+
+        ManeuverNode {
+            time: time,
+            prograde: delta_v.perp_dot(radial) / determinant,
+            radial: prograde.perp_dot(delta_v) / determinant,
+        }
+    }
+}
+
+/// Upcoming burns, soonest first.
+#[derive(Component, Default)]
+pub struct ManeuverPlan {
+    pub nodes: Vec<ManeuverNode>,
+}
+
+impl ManeuverPlan {
+    pub fn add(&mut self, node: ManeuverNode) {
+        self.nodes.push(node);
+        self.nodes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+}
+
+/// The node that circularizes the orbit at its next apoapsis or periapsis.
+pub fn circularize_node(orbit: &Orbit, primary_mass: f32, time: f32, at_apoapsis: bool) -> Option<ManeuverNode> {
+    let (radius, mean_anomaly) = if at_apoapsis {
+        (apoapsis(orbit), PI)
+    } else {
+        (periapsis(orbit), 0.0)
+    };
+    if !radius.is_finite() || !orbit.period.is_finite() {
+        return None;
+    }
+
+    Some(ManeuverNode {
+        time: time + time_to_mean_anomaly(orbit, time, mean_anomaly),
+        prograde: circular_speed(primary_mass, radius) - speed_at_radius(orbit, primary_mass, radius),
+        radial: 0.0,
+    })
+}
+
+/// Turns circularize requests into a node and flies it.
+pub fn plan_circularization(
+    time: Res<Time>,
+    masses: Query<&Mass>,
+    mut ships: Query<(&Name, &mut Autopilot, &mut ManeuverPlan, Option<&Orbit>)>,
+) {
+    for (name, mut autopilot, mut plan, orbit) in ships.iter_mut() {
+        let at_apoapsis = match autopilot.mode {
+            AutopilotMode::CircularizeApoapsis => true,
+            AutopilotMode::CircularizePeriapsis => false,
+            _ => continue,
+        };
+
+        let node = orbit.and_then(|orbit| {
+            let primary = masses.get(orbit.planet).ok()?;
+            circularize_node(orbit, primary.value, time.elapsed_seconds(), at_apoapsis)
+        });
+
+        match node {
+            Some(node) => {
+                info!("{}: circularizing in {:.1}s, dv {:.2}", name, node.time - time.elapsed_seconds(), node.prograde);
+                plan.add(node);
+                autopilot.engage(AutopilotMode::ExecuteNode);
+            },
+            None => {
+                warn!("{}: no apsis to circularize at", name);
+                autopilot.engage(AutopilotMode::Off);
+            },
+        }
+    }
+}
+
+/// Turn command that brings `heading` round to `target` as fast as the
+/// ship's torque allows without overshooting.
+fn attitude_turn(heading: Vec2, target: Vec2, angvel: f32, max_accel: f32) -> f32 {
+    if target == Vec2::ZERO || max_accel <= 0.0 {
+        return 0.0;
+    }
+    let error = heading.angle_between(target);
+    let wanted_rate = error.signum() * (max_accel * error.abs()).sqrt();
+    ((wanted_rate - angvel) / (max_accel * ATTITUDE_RESPONSE)).clamp(-1.0, 1.0)
+}
+
+pub fn run_autopilot(
+    time: Res<Time>,
+    planets: Query<&Velocity, (With<Planet>, Without<Autopilot>)>,
+    mut ships: Query<(
+        &Name,
+        &mut Autopilot,
+        &mut ManeuverPlan,
+        &mut ShipControl,
+        &TileSet,
+        &Transform,
+        &Velocity,
+        &ReadMassProperties,
+        &AppliedThrust,
+        Option<&Orbit>,
+    )>,
+) {
+    let now = time.elapsed_seconds();
+    let dt = time.delta_seconds();
+
+    for (name, mut autopilot, mut plan, mut control, tileset, transform, velocity, mass_props, applied, orbit) in ships.iter_mut() {
+        if autopilot.mode == AutopilotMode::Off {
+            continue;
+        }
+
+        let mass = mass_props.0.mass;
+        let (_, max_thrust) = engine_totals(tileset);
+        let engines = thrust_direction(tileset);
+        let forward = if engines == Vec2::ZERO { Vec2::Y } else { engines };
+        let heading = (transform.rotation * forward.extend(0.0)).truncate();
+
+        let primary_velocity = orbit
+            .and_then(|orbit| planets.get(orbit.planet).ok())
+            .map_or(Vec2::ZERO, |v| v.linvel);
+        let relative = velocity.linvel - primary_velocity;
+
+        let target = match autopilot.mode {
+            AutopilotMode::Prograde => relative.normalize_or_zero(),
+            AutopilotMode::Retrograde => -relative.normalize_or_zero(),
+            AutopilotMode::ExecuteNode => {
+                // Take off the thrust the last step actually delivered
+                if let Some(burn) = autopilot.burn.as_mut() {
+                    burn.remaining -= applied.force / mass.max(f32::EPSILON) * dt;
+                }
+                if autopilot.burn.map_or(false, |burn| burn.done()) {
+                    info!("{}: burn complete", name);
+                    autopilot.burn = None;
+                }
+
+                if autopilot.burn.is_none() {
+                    let Some(node) = plan.nodes.first().copied() else {
+                        info!("{}: no more nodes", name);
+                        autopilot.engage(AutopilotMode::Off);
+                        control.throttle = 0.0;
+                        continue;
+                    };
+                    if let Some(orbit) = orbit {
+                        let delta_v = node.world_delta_v(orbit);
+                        let duration = if max_thrust > 0.0 { node.delta_v() * mass / max_thrust } else { 0.0 };
+                        if now >= node.time - duration * 0.5 {
+                            info!("{}: burning {:.2}", name, node.delta_v());
+                            plan.nodes.remove(0);
+                            autopilot.burn = Some(Burn {
+                                direction: delta_v.normalize_or_zero(),
+                                remaining: delta_v,
+                            });
+                        }
+                        // Line up ahead of the burn
+                        delta_v.normalize_or_zero()
+                    } else {
+                        Vec2::ZERO
+                    }
+                } else {
+                    autopilot.burn.map_or(Vec2::ZERO, |burn| burn.remaining.normalize_or_zero())
+                }
+            },
+            _ => Vec2::ZERO,
+        };
+
+        let max_accel = turn_torque(tileset) / mass_props.0.principal_inertia.max(f32::EPSILON);
+        control.turn = attitude_turn(heading, target, velocity.angvel, max_accel);
+
+        if autopilot.mode == AutopilotMode::ExecuteNode {
+            control.throttle = match autopilot.burn {
+                Some(burn) if heading.dot(target) > BURN_ALIGNMENT && max_thrust > 0.0 && dt > 0.0 => {
+                    // Ease off so the last step doesn't overshoot
+                    (burn.remaining.length() * mass / (max_thrust * dt)).min(1.0)
+                },
+                _ => 0.0,
+            };
+        }
+    }
+}
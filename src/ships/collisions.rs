@@ -0,0 +1,218 @@
+//! Turns Rapier's collision and contact-force events into gameplay events
+//! about ships, so damage, landing, sound and the UI don't each have to pick
+//! apart collider pairs.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::landing::{surface_velocity, ApproachVelocity};
+use super::ship::Ship;
+use super::tiles::*;
+use crate::planets::planet::Planet;
+
+/// Contact forces below this aren't reported; resting contacts stay quiet.
+pub const CONTACT_FORCE_THRESHOLD: f32 = 50.0;
+/// Ships bumping slower than this, like when docking, do no damage.
+pub const SHIP_DAMAGE_SPEED: f32 = 4.0;
+/// Ship-on-ship impacts faster than this destroy the tiles that hit outright.
+pub const SHIP_DESTRUCTIVE_SPEED: f32 = 12.0;
+
+/// A ship started touching a planet this step.
+#[derive(Debug, Clone)]
+pub struct ShipHitPlanet {
+    pub ship: Entity,
+    pub planet: Entity,
+    /// The ship's velocity going into the step, relative to the surface
+    pub relative_velocity: Vec2,
+    pub speed: f32,
+    /// From Rapier's contact forces; zero for contacts too gentle to report
+    pub impulse: f32,
+    /// The ship's tiles that touched
+    pub tiles: Vec<Pos>,
+    pub point: Vec2,
+}
+
+/// Two ships started touching this step.
+#[derive(Debug, Clone)]
+pub struct ShipHitShip {
+    pub ship: Entity,
+    pub other: Entity,
+    /// `ship`'s velocity going into the step, relative to `other`'s
+    pub relative_velocity: Vec2,
+    pub speed: f32,
+    pub impulse: f32,
+    pub tiles: Vec<Pos>,
+    pub other_tiles: Vec<Pos>,
+    pub point: Vec2,
+}
+
+/// A tile was knocked off a ship, by whatever cause.
+#[derive(Debug, Clone)]
+pub struct TileDestroyed {
+    pub ship: Entity,
+    pub pos: Pos,
+    pub kind: TileKind,
+    pub point: Vec2,
+}
+
+/// Damages the `hit` tiles of `ship`, or destroys them, sending a
+/// `TileDestroyed` for each one removed.
+pub fn damage_tiles(
+    ship: Entity,
+    transform: &GlobalTransform,
+    tileset: &mut TileSet,
+    hit: &[Pos],
+    destroy: bool,
+    destroyed: &mut EventWriter<TileDestroyed>,
+) {
+    for &pos in hit {
+        let Some(kind) = tileset.tiles.get(&pos).map(|tile| tile.kind) else {
+            continue;
+        };
+        if tileset.damage(pos, destroy) {
+            let point = transform.transform_point(Vec3::new(pos.0 as f32, pos.1 as f32, 0.0)).truncate();
+            destroyed.send(TileDestroyed {
+                ship: ship,
+                pos: pos,
+                kind: kind,
+                point: point,
+            });
+        }
+    }
+}
+
+/// The first point Rapier has for a contact between two colliders.
+fn contact_point(context: &RapierContext, a: Entity, b: Entity) -> Option<Vec2> {
+    let pair = context.contact_pair(a, b)?;
+    pair.manifolds()
+        .find_map(|manifold| manifold.solver_contacts().next().map(|contact| contact.point()))
+}
+
+/// Everything that touched between one ship and one other body this step.
+#[derive(Default)]
+struct Contact {
+    tiles: Vec<Pos>,
+    other_tiles: Vec<Pos>,
+    point: Option<Vec2>,
+}
+
+pub fn classify_contacts(
+    time: Res<Time>,
+    context: Res<RapierContext>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut forces: EventReader<ContactForceEvent>,
+    mut planet_hits: EventWriter<ShipHitPlanet>,
+    mut ship_hits: EventWriter<ShipHitShip>,
+    tiles: Query<(&TileMarker, &Parent, &GlobalTransform)>,
+    planets: Query<(&GlobalTransform, &Velocity), With<Planet>>,
+    ships: Query<(&GlobalTransform, &ApproachVelocity), With<Ship>>,
+) {
+    // Impulse per pair of bodies, from the forces over this step
+    let mut impulses: HashMap<(Entity, Entity), f32> = HashMap::new();
+    for event in forces.iter() {
+        let body = |collider: Entity| tiles.get(collider).map_or(collider, |(_, parent, _)| parent.get());
+        let (a, b) = (body(event.collider1), body(event.collider2));
+        let key = if a < b { (a, b) } else { (b, a) };
+        *impulses.entry(key).or_default() += event.total_force_magnitude * time.delta_seconds();
+    }
+
+    let mut contacts: HashMap<(Entity, Entity), Contact> = HashMap::new();
+    for event in collisions.iter() {
+        let CollisionEvent::Started(a, b, _) = event else {
+            continue;
+        };
+        let point = contact_point(&context, *a, *b);
+
+        match (tiles.get(*a), tiles.get(*b)) {
+            (Ok((first, first_ship, first_transform)), Ok((second, second_ship, _))) => {
+                let (first_ship, second_ship) = (first_ship.get(), second_ship.get());
+                if first_ship == second_ship {
+                    continue;
+                }
+                // One entry per pair of ships, whichever way round Rapier reports them
+                let (key, mine, theirs) = if first_ship < second_ship {
+                    ((first_ship, second_ship), first.pos, second.pos)
+                } else {
+                    ((second_ship, first_ship), second.pos, first.pos)
+                };
+                let contact = contacts.entry(key).or_default();
+                contact.tiles.push(mine);
+                contact.other_tiles.push(theirs);
+                contact.point = contact.point.or(point).or(Some(first_transform.translation().truncate()));
+            },
+            (Ok((tile, ship, transform)), Err(_)) | (Err(_), Ok((tile, ship, transform))) => {
+                let other = if tiles.contains(*a) { *b } else { *a };
+                if !planets.contains(other) {
+                    continue;
+                }
+                let contact = contacts.entry((ship.get(), other)).or_default();
+                contact.tiles.push(tile.pos);
+                contact.point = contact.point.or(point).or(Some(transform.translation().truncate()));
+            },
+            _ => {},
+        }
+    }
+
+    for ((ship, other), contact) in contacts {
+        let Ok((transform, approach)) = ships.get(ship) else {
+            continue;
+        };
+        let impulse = impulses.get(&if ship < other { (ship, other) } else { (other, ship) }).copied().unwrap_or(0.0);
+        let point = contact.point.unwrap_or(transform.translation().truncate());
+
+        if let Ok((planet_transform, planet_velocity)) = planets.get(other) {
+            let offset = (transform.translation() - planet_transform.translation()).truncate();
+            let relative_velocity = approach.0 - surface_velocity(planet_velocity, offset);
+            planet_hits.send(ShipHitPlanet {
+                ship: ship,
+                planet: other,
+                relative_velocity: relative_velocity,
+                speed: relative_velocity.length(),
+                impulse: impulse,
+                tiles: contact.tiles,
+                point: point,
+            });
+        } else if let Ok((_, other_approach)) = ships.get(other) {
+            let relative_velocity = approach.0 - other_approach.0;
+            ship_hits.send(ShipHitShip {
+                ship: ship,
+                other: other,
+                relative_velocity: relative_velocity,
+                speed: relative_velocity.length(),
+                impulse: impulse,
+                tiles: contact.tiles,
+                other_tiles: contact.other_tiles,
+                point: point,
+            });
+        }
+    }
+}
+
+/// Ships that run into each other too fast damage the tiles that touched,
+/// on both sides.
+pub fn damage_ship_collisions(
+    mut commands: Commands,
+    mut hits: EventReader<ShipHitShip>,
+    mut destroyed: EventWriter<TileDestroyed>,
+    mut ships: Query<(&Name, &GlobalTransform, &mut TileSet), With<Ship>>,
+) {
+    for hit in hits.iter() {
+        if hit.speed < SHIP_DAMAGE_SPEED {
+            continue;
+        }
+        let destroy = hit.speed >= SHIP_DESTRUCTIVE_SPEED;
+
+        for (ship, tiles) in [(hit.ship, &hit.tiles), (hit.other, &hit.other_tiles)] {
+            let Ok((name, transform, mut tileset)) = ships.get_mut(ship) else {
+                continue;
+            };
+            warn!("{} collided at {:.2}", name, hit.speed);
+            damage_tiles(ship, transform, &mut tileset, tiles, destroy, &mut destroyed);
+            if tileset.tiles.is_empty() {
+                commands.entity(ship).despawn_recursive();
+            }
+        }
+    }
+}
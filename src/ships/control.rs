@@ -1,2 +1,68 @@
-#[derive(Debug, Clone, Copy, Default)]
-pub struct ShipControl;
+use bevy::prelude::*;
+
+use super::autopilot::{Autopilot, AutopilotMode};
+use super::ship::Player;
+use crate::input::InputBindings;
+
+/// What a ship is being asked to do this frame, from the keyboard or an AI.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ShipControl {
+    /// 0.0 to 1.0 of full engine thrust
+    pub throttle: f32,
+    /// -1.0 (clockwise) to 1.0 (counter-clockwise)
+    pub turn: f32,
+    /// Trigger held for guns and for missile launchers; each fires whenever
+    /// it's reloaded
+    pub fire_guns: bool,
+    pub fire_missiles: bool,
+}
+
+/// Keyboard flight controls, from the axes and actions in `config/input.ron`.
+pub fn player_control_system(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut controls: Query<&mut ShipControl, With<Player>>,
+) {
+    for mut control in controls.iter_mut() {
+        control.throttle = bindings.axis(&keys, "thrust").max(0.0);
+        // Positive turns right, i.e. clockwise
+        control.turn = -bindings.axis(&keys, "turn");
+        control.fire_guns = bindings.pressed(&keys, "fire_guns");
+        control.fire_missiles = bindings.pressed(&keys, "fire_missiles");
+    }
+}
+
+/// The `autopilot_*` actions pick an autopilot mode; `autopilot_off`, or
+/// steering by hand, hands control back.
+pub fn player_autopilot_system(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut autopilots: Query<&mut Autopilot, With<Player>>,
+) {
+    if bindings.axis(&keys, "turn") != 0.0 {
+        for mut autopilot in autopilots.iter_mut() {
+            if autopilot.mode != AutopilotMode::Off {
+                info!("Autopilot: Off, steering by hand");
+                autopilot.engage(AutopilotMode::Off);
+            }
+        }
+    }
+
+    let modes = [
+        ("autopilot_off", AutopilotMode::Off),
+        ("autopilot_prograde", AutopilotMode::Prograde),
+        ("autopilot_retrograde", AutopilotMode::Retrograde),
+        ("autopilot_circularize_apoapsis", AutopilotMode::CircularizeApoapsis),
+        ("autopilot_circularize_periapsis", AutopilotMode::CircularizePeriapsis),
+        ("autopilot_execute_node", AutopilotMode::ExecuteNode),
+    ];
+
+    for (action, mode) in modes {
+        if bindings.just_pressed(&keys, action) {
+            for mut autopilot in autopilots.iter_mut() {
+                info!("Autopilot: {:?}", mode);
+                autopilot.engage(mode);
+            }
+        }
+    }
+}
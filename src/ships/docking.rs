@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::ship::{spawn_ship, Player, Ship};
+use super::tiles::*;
+use crate::physics::orbits::Orbit;
+
+/// Largest gap between two port faces, in tiles, that still counts as docked.
+pub const DOCKING_TOLERANCE: f32 = 0.3;
+/// Ports must face each other to within about 18 degrees.
+pub const DOCKING_ALIGNMENT: f32 = -0.95;
+pub const MAX_DOCKING_SPEED: f32 = 2.0;
+pub const UNDOCK_SPEED: f32 = 0.5;
+pub const UNDOCK_COOLDOWN: f32 = 3.0;
+
+/// A ship that was merged into this one, kept so it can be split off again.
+/// Tiles are in the host's grid.
+pub struct DockedPart {
+    pub name: String,
+    pub tiles: Vec<Tile>,
+    pub host_port: Pos,
+}
+
+#[derive(Component, Default)]
+pub struct Docked {
+    pub parts: Vec<DockedPart>,
+}
+
+/// Ships that have just undocked don't dock again until this runs out.
+#[derive(Component)]
+pub struct DockingCooldown(pub Timer);
+
+/// Split the most recently docked part off `ship`.
+pub struct UndockRequest {
+    pub ship: Entity,
+}
+
+pub fn tick_docking_cooldown(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut cooldowns: Query<(Entity, &mut DockingCooldown)>,
+) {
+    for (ship, mut cooldown) in cooldowns.iter_mut() {
+        if cooldown.0.tick(time.delta()).finished() {
+            commands.entity(ship).remove::<DockingCooldown>();
+        }
+    }
+}
+
+struct PortState {
+    ship: Entity,
+    pos: Pos,
+    facing: Facing,
+    face: Vec2,
+    normal: Vec2,
+}
+
+pub fn detect_docking(
+    mut commands: Commands,
+    ports: Query<(&DockingPort, &TileMarker, &GlobalTransform, &Parent)>,
+    mut ships: Query<(Entity, &Name, &mut TileSet, &mut Velocity, &ReadMassProperties, Option<&mut Docked>, Option<&Player>), (With<Ship>, Without<DockingCooldown>)>,
+) {
+    let states: Vec<PortState> = ports.iter()
+        .filter(|(_, _, _, parent)| ships.contains(parent.get()))
+        .map(|(port, marker, transform, parent)| {
+            let (_, rotation, translation) = transform.to_scale_rotation_translation();
+            let normal = (rotation * port.facing.vector().as_vec2().extend(0.0)).truncate();
+            PortState {
+                ship: parent.get(),
+                pos: marker.pos,
+                facing: port.facing,
+                face: translation.truncate() + normal * 0.5,
+                normal: normal,
+            }
+        }).collect();
+
+    let mut merged: Vec<Entity> = Vec::new();
+
+    for (i, a) in states.iter().enumerate() {
+        for b in states[i + 1..].iter() {
+            if a.ship == b.ship || merged.contains(&a.ship) || merged.contains(&b.ship) {
+                continue;
+            }
+            if a.face.distance(b.face) > DOCKING_TOLERANCE || a.normal.dot(b.normal) > DOCKING_ALIGNMENT {
+                continue;
+            }
+
+            let Ok([host, guest]) = ships.get_many_mut([a.ship, b.ship]) else {
+                continue;
+            };
+            if (host.3.linvel - guest.3.linvel).length() > MAX_DOCKING_SPEED {
+                continue;
+            }
+
+            // The player's ship, or else the bigger one, keeps its body
+            let keep_first = match (host.6.is_some(), guest.6.is_some()) {
+                (true, false) => true,
+                (false, true) => false,
+                _ => host.2.tiles.len() >= guest.2.tiles.len(),
+            };
+            let (host, guest, host_port, guest_port) = if keep_first {
+                (host, guest, a, b)
+            } else {
+                (guest, host, b, a)
+            };
+
+            let (host_entity, _, mut host_tiles, mut host_vel, host_mass, host_docked, _) = host;
+            let (guest_entity, guest_name, guest_tiles, guest_vel, guest_mass, _, _) = guest;
+
+            let Some(tiles) = dock_tiles(&host_tiles, host_port.pos, host_port.facing, &guest_tiles, guest_port.pos, guest_port.facing) else {
+                continue;
+            };
+
+            let (m1, m2) = (host_mass.0.mass, guest_mass.0.mass);
+            if m1 + m2 > 0.0 {
+                host_vel.linvel = (host_vel.linvel * m1 + guest_vel.linvel * m2) / (m1 + m2);
+                host_vel.angvel = (host_vel.angvel * m1 + guest_vel.angvel * m2) / (m1 + m2);
+            }
+
+            for tile in tiles.iter() {
+                host_tiles.tiles.insert(tile.pos, tile.clone());
+            }
+
+            let part = DockedPart {
+                name: guest_name.to_string(),
+                tiles: tiles,
+                host_port: host_port.pos,
+            };
+            match host_docked {
+                Some(mut docked) => docked.parts.push(part),
+                None => {
+                    commands.entity(host_entity).insert(Docked { parts: vec![part] });
+                },
+            }
+
+            info!("Docked {} into {:?}", guest_name, host_entity);
+
+            commands.entity(host_entity).remove::<Orbit>();
+            commands.entity(guest_entity).despawn_recursive();
+            merged.push(host_entity);
+            merged.push(guest_entity);
+        }
+    }
+}
+
+/// Map the guest's tiles into the host's grid so the two ports sit face to
+/// face, or `None` if any tile would overlap.
+pub fn dock_tiles(
+    host: &TileSet,
+    host_port: Pos,
+    host_facing: Facing,
+    guest: &TileSet,
+    guest_port: Pos,
+    guest_facing: Facing,
+) -> Option<Vec<Tile>> {
+    let wanted = host_facing.rotated(2);
+    let quarter_turns = (0..4).find(|&q| guest_facing.rotated(q) == wanted)?;
+
+    let anchor = IVec2::from(host_port) + host_facing.vector();
+    let guest_origin = IVec2::from(guest_port);
+
+    let tiles: Vec<Tile> = guest.tiles.values().map(|tile| {
+        let pos = anchor + rotate_quarter(IVec2::from(tile.pos) - guest_origin, quarter_turns);
+        Tile {
+            pos: (pos.x, pos.y),
+            facing: tile.facing.rotated(quarter_turns),
+            ..tile.clone()
+        }
+    }).collect();
+
+    if tiles.iter().any(|tile| host.tiles.contains_key(&tile.pos)) {
+        return None;
+    }
+
+    Some(tiles)
+}
+
+/// Takes `part`'s tiles back out of the host's set. The host's current
+/// copies are returned, with any fuel burned since docking.
+pub fn split_tiles(host: &mut TileSet, part: &DockedPart) -> Vec<Tile> {
+    part.tiles.iter()
+        .filter_map(|tile| host.tiles.remove(&tile.pos))
+        .collect()
+}
+
+pub fn undock(
+    mut commands: Commands,
+    mut requests: EventReader<UndockRequest>,
+    mut ships: Query<(&mut TileSet, &mut Docked, &Transform, &Velocity)>,
+) {
+    for request in requests.iter() {
+        let Ok((mut tileset, mut docked, transform, velocity)) = ships.get_mut(request.ship) else {
+            continue;
+        };
+        let Some(part) = docked.parts.pop() else {
+            continue;
+        };
+
+        let tiles = split_tiles(&mut tileset, &part);
+
+        let host_facing = tileset.tiles.get(&part.host_port).map_or(Facing::Up, |tile| tile.facing);
+        let push = (transform.rotation * host_facing.vector().as_vec2().extend(0.0)).truncate();
+
+        // Keep the part's tiles in the host's grid and give it the host's
+        // transform, so nothing moves on the frame of the split.
+        let split = spawn_ship(
+            &mut commands,
+            &part.name,
+            TileSet::from(tiles),
+            transform.translation.truncate(),
+            velocity.linvel + push * UNDOCK_SPEED,
+        );
+        commands.entity(split).insert((
+            *transform,
+            DockingCooldown(Timer::from_seconds(UNDOCK_COOLDOWN, TimerMode::Once)),
+        ));
+
+        commands.entity(request.ship)
+            .remove::<Orbit>()
+            .insert(DockingCooldown(Timer::from_seconds(UNDOCK_COOLDOWN, TimerMode::Once)));
+
+        info!("Undocked {} from {:?}", part.name, request.ship);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(pos: Pos, kind: TileKind, facing: Facing) -> Tile {
+        Tile { pos: pos, kind: kind, facing: facing, fuel: None, damaged: false }
+    }
+
+    fn host() -> TileSet {
+        TileSet::from(vec![
+            tile((0, 0), TileKind::Hull, Facing::Up),
+            tile((0, 1), TileKind::DockingPort, Facing::Up),
+        ])
+    }
+
+    /// A port facing right with a hull and an engine trailing to its left.
+    fn guest() -> TileSet {
+        TileSet::from(vec![
+            tile((0, 0), TileKind::DockingPort, Facing::Right),
+            tile((-1, 0), TileKind::Hull, Facing::Up),
+            tile((-2, 0), TileKind::Engine, Facing::Down),
+        ])
+    }
+
+    fn positions<'a>(tiles: impl IntoIterator<Item = &'a Tile>) -> Vec<Pos> {
+        let mut positions: Vec<Pos> = tiles.into_iter().map(|tile| tile.pos).collect();
+        positions.sort();
+        positions
+    }
+
+    #[test]
+    fn guest_is_turned_to_face_the_host_port() {
+        let tiles = dock_tiles(&host(), (0, 1), Facing::Up, &guest(), (0, 0), Facing::Right).unwrap();
+        let find = |pos: Pos| tiles.iter().find(|tile| tile.pos == pos).unwrap();
+
+        // Three quarter turns put the guest's port just above the host's, facing down
+        assert_eq!(positions(&tiles), vec![(0, 2), (0, 3), (0, 4)]);
+        assert_eq!(find((0, 2)).kind, TileKind::DockingPort);
+        assert_eq!(find((0, 2)).facing, Facing::Down);
+        assert_eq!(find((0, 3)).kind, TileKind::Hull);
+        assert_eq!(find((0, 4)).kind, TileKind::Engine);
+        assert_eq!(find((0, 4)).facing, Facing::Left);
+    }
+
+    #[test]
+    fn overlapping_tiles_refuse_to_dock() {
+        let mut host = host();
+        host.tiles.insert((0, 3), tile((0, 3), TileKind::Hull, Facing::Up));
+        assert!(dock_tiles(&host, (0, 1), Facing::Up, &guest(), (0, 0), Facing::Right).is_none());
+    }
+
+    #[test]
+    fn undocking_restores_both_sets() {
+        let mut merged = host();
+        let tiles = dock_tiles(&merged, (0, 1), Facing::Up, &guest(), (0, 0), Facing::Right).unwrap();
+        for tile in tiles.iter() {
+            merged.tiles.insert(tile.pos, tile.clone());
+        }
+        assert_eq!(merged.tiles.len(), 5);
+
+        let part = DockedPart {
+            name: "Guest".to_string(),
+            tiles: tiles.clone(),
+            host_port: (0, 1),
+        };
+        let split = split_tiles(&mut merged, &part);
+
+        assert_eq!(positions(merged.tiles.values()), positions(host().tiles.values()));
+        assert_eq!(positions(&split), positions(&tiles));
+        for (tile, original) in split.iter().zip(tiles.iter()) {
+            assert_eq!((tile.kind, tile.facing), (original.kind, original.facing));
+        }
+    }
+}
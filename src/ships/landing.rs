@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::collisions::{damage_tiles, ShipHitPlanet, TileDestroyed};
+use super::control::ShipControl;
+use super::ship::Ship;
+use super::tiles::*;
+use crate::physics::gravity::Landed;
+use crate::physics::orbits::Orbit;
+use crate::planets::planet::Planet;
+
+/// Fastest touchdown, relative to the surface, that counts as a landing.
+pub const MAX_LANDING_SPEED: f32 = 3.0;
+/// Touchdowns faster than this destroy the tiles that hit outright.
+pub const DESTRUCTIVE_SPEED: f32 = 10.0;
+
+/// A ship hit a planet too fast to land.
+pub struct ShipCrashed {
+    pub ship: Entity,
+    pub planet: Entity,
+    pub speed: f32,
+}
+
+/// A ship's velocity going into the physics step. Contacts only show up
+/// after the step has already slowed the ship, so touchdowns are judged on this.
+#[derive(Component, Default)]
+pub struct ApproachVelocity(pub Vec2);
+
+/// Velocity of the surface of a body at `offset` from its centre.
+pub fn surface_velocity(velocity: &Velocity, offset: Vec2) -> Vec2 {
+    velocity.linvel + velocity.angvel * offset.perp()
+}
+
+pub fn record_approach_velocity(
+    mut commands: Commands,
+    mut ships: Query<(Entity, &Velocity, Option<&mut ApproachVelocity>), With<Ship>>,
+) {
+    for (ship, velocity, approach) in ships.iter_mut() {
+        match approach {
+            Some(mut approach) => approach.0 = velocity.linvel,
+            None => {
+                commands.entity(ship).insert(ApproachVelocity(velocity.linvel));
+            },
+        }
+    }
+}
+
+/// Lands ships that touch a planet slowly enough, and damages the tiles of
+/// those that don't.
+pub fn detect_touchdown(
+    mut commands: Commands,
+    mut hits: EventReader<ShipHitPlanet>,
+    mut crashes: EventWriter<ShipCrashed>,
+    mut destroyed: EventWriter<TileDestroyed>,
+    planets: Query<&GlobalTransform, With<Planet>>,
+    mut ships: Query<(&Name, &GlobalTransform, &mut TileSet), (With<Ship>, Without<Landed>)>,
+) {
+    for hit in hits.iter() {
+        let (Ok((name, transform, mut tileset)), Ok(planet_transform)) = (ships.get_mut(hit.ship), planets.get(hit.planet)) else {
+            continue;
+        };
+
+        // Only count contacts the ship was moving into, not ones from taking off
+        let offset = (transform.translation() - planet_transform.translation()).truncate();
+        if hit.relative_velocity.dot(offset) >= 0.0 {
+            continue;
+        }
+
+        if hit.speed <= MAX_LANDING_SPEED {
+            info!("{} landed at {:.2}", name, hit.speed);
+            commands.entity(hit.ship)
+                .insert(Landed {
+                    planet: hit.planet,
+                    local: transform.reparented_to(planet_transform),
+                })
+                .insert(RigidBody::KinematicPositionBased)
+                .remove::<Orbit>();
+            continue;
+        }
+
+        warn!("{} crashed at {:.2}", name, hit.speed);
+        crashes.send(ShipCrashed {
+            ship: hit.ship,
+            planet: hit.planet,
+            speed: hit.speed,
+        });
+        let destroy = hit.speed >= DESTRUCTIVE_SPEED;
+        damage_tiles(hit.ship, transform, &mut tileset, &hit.tiles, destroy, &mut destroyed);
+        if tileset.tiles.is_empty() {
+            commands.entity(hit.ship).despawn_recursive();
+        }
+    }
+}
+
+/// Any throttle lifts a landed ship off, starting it at the surface's velocity.
+pub fn take_off(
+    mut commands: Commands,
+    planets: Query<(&GlobalTransform, &Velocity), With<Planet>>,
+    ships: Query<(Entity, &Name, &Landed, &GlobalTransform, &ShipControl)>,
+) {
+    for (ship, name, landed, transform, control) in ships.iter() {
+        if control.throttle <= 0.0 {
+            continue;
+        }
+
+        let velocity = planets.get(landed.planet).map_or(Vec2::ZERO, |(planet_transform, planet_velocity)| {
+            let offset = (transform.translation() - planet_transform.translation()).truncate();
+            surface_velocity(planet_velocity, offset)
+        });
+
+        info!("{} taking off", name);
+        commands.entity(ship)
+            .remove::<Landed>()
+            .insert(RigidBody::Dynamic)
+            .insert(Velocity {
+                linvel: velocity,
+                ..default()
+            });
+    }
+}
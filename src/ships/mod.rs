@@ -1,2 +1,72 @@
+use bevy::{
+    prelude::*,
+    time::common_conditions::on_timer,
+    utils::Duration,
+};
+
+use crate::common::SparkSet;
+
+pub mod autopilot;
+pub mod collisions;
+pub mod control;
+pub mod docking;
+pub mod landing;
+pub mod propulsion;
 pub mod ship;
+pub mod targeting;
 pub mod tiles;
+pub mod traffic;
+pub mod transfer;
+pub mod weapons;
+
+pub struct SparkShipsPlugin;
+
+impl Plugin for SparkShipsPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .add_event::<docking::UndockRequest>()
+            .add_event::<landing::ShipCrashed>()
+            .add_event::<collisions::ShipHitPlanet>()
+            .add_event::<collisions::ShipHitShip>()
+            .add_event::<collisions::TileDestroyed>()
+            .add_event::<transfer::TransferRequest>()
+            .add_systems((
+                docking::tick_docking_cooldown,
+                docking::detect_docking.before(tiles::make_tiles_system),
+                docking::undock.before(tiles::make_tiles_system),
+                collisions::classify_contacts,
+                collisions::damage_ship_collisions
+                    .after(collisions::classify_contacts)
+                    .before(tiles::make_tiles_system),
+                landing::detect_touchdown
+                    .after(collisions::classify_contacts)
+                    .before(tiles::make_tiles_system),
+                traffic::spawn_traffic,
+                traffic::despawn_lost_npcs.after(landing::detect_touchdown),
+                weapons::detect_hits.before(tiles::make_tiles_system),
+                weapons::expire_projectiles,
+                tiles::make_tiles_system,
+            ).in_set(SparkSet::Spawn))
+            .add_systems((
+                autopilot::plan_circularization,
+                transfer::plan_transfers.before(autopilot::run_autopilot),
+                autopilot::run_autopilot
+                    .after(autopilot::plan_circularization)
+                    .before(propulsion::apply_thrust),
+            ).in_set(SparkSet::Forces))
+            .add_systems((
+                targeting::update_target_info,
+                weapons::update_lead_solutions.run_if(on_timer(Duration::from_secs_f32(0.1))),
+            ).in_set(SparkSet::Navigation))
+            .add_systems((
+                weapons::fire_weapons,
+                weapons::guide_missiles,
+                propulsion::apply_thrust,
+                propulsion::sync_tank_mass.after(propulsion::apply_thrust),
+                landing::take_off,
+                landing::record_approach_velocity,
+            ).in_set(SparkSet::Forces));
+    }
+}
@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::control::ShipControl;
+use super::tiles::*;
+
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+/// Reaction-wheel torque for turning, at `ShipControl::turn` of 1.0, before
+/// any RCS tiles.
+pub const TURN_TORQUE: f32 = 4.0;
+
+/// The thrust `apply_thrust` added to `ExternalForce` this frame, in world space.
+#[derive(Component, Default)]
+pub struct AppliedThrust {
+    pub force: Vec2,
+}
+
+pub fn exhaust_velocity(isp: f32) -> f32 {
+    isp * STANDARD_GRAVITY
+}
+
+/// Thrust-weighted exhaust velocity of all engines, and their total thrust.
+pub fn engine_totals(tileset: &TileSet) -> (f32, f32) {
+    let engines = tileset.tiles.values().filter(|tile| tile.kind.thrust() > 0.0);
+    let (thrust, flow) = engines.fold((0.0, 0.0), |(thrust, flow), tile| {
+        let t = tile.kind.thrust();
+        (thrust + t, flow + t / exhaust_velocity(tile.kind.isp()))
+    });
+    if flow > 0.0 { (thrust / flow, thrust) } else { (0.0, 0.0) }
+}
+
+/// Which way the engines push, in the ship's frame; zero without engines.
+pub fn thrust_direction(tileset: &TileSet) -> Vec2 {
+    tileset.tiles.values()
+        .filter(|tile| tile.kind.thrust() > 0.0)
+        .map(|tile| -tile.facing.vector().as_vec2() * tile.kind.thrust())
+        .sum::<Vec2>()
+        .normalize_or_zero()
+}
+
+pub fn turn_torque(tileset: &TileSet) -> f32 {
+    TURN_TORQUE + tileset.tiles.values().map(|tile| tile.kind.torque()).sum::<f32>()
+}
+
+pub fn fuel_totals(tileset: &TileSet) -> (f32, f32) {
+    tileset.tiles.values().fold((0.0, 0.0), |(fuel, capacity), tile| {
+        (fuel + tile.fuel(), capacity + tile.kind.fuel_capacity())
+    })
+}
+
+/// Remaining delta-v from the Tsiolkovsky rocket equation, burning every
+/// tank through every engine.
+pub fn delta_v(tileset: &TileSet) -> f32 {
+    let (exhaust, _) = engine_totals(tileset);
+    let wet: f32 = tileset.tiles.values().map(|tile| tile.mass()).sum();
+    let (fuel, _) = fuel_totals(tileset);
+    let dry = wet - fuel;
+    if exhaust <= 0.0 || dry <= 0.0 {
+        return 0.0;
+    }
+    exhaust * (wet / dry).ln()
+}
+
+/// Burns fuel for the requested throttle, adding thrust and turning torque
+/// on top of gravity. Thrust acts through the centre of mass.
+pub fn apply_thrust(
+    time: Res<Time>,
+    mut ships: Query<(&ShipControl, &mut TileSet, &Transform, &mut ExternalForce, &mut AppliedThrust)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (control, mut tileset, transform, mut ext_force, mut applied) in ships.iter_mut() {
+        ext_force.torque = control.turn * turn_torque(&tileset);
+        applied.force = Vec2::ZERO;
+
+        let (exhaust, max_thrust) = engine_totals(&tileset);
+        if control.throttle <= 0.0 || max_thrust <= 0.0 {
+            continue;
+        }
+
+        let wanted = max_thrust * control.throttle.min(1.0);
+        let needed = wanted / exhaust * dt;
+        let (fuel, _) = fuel_totals(&tileset);
+        if fuel <= 0.0 {
+            continue;
+        }
+        let burned = needed.min(fuel);
+
+        // Draining fuel isn't a shape change, so don't rebuild the colliders
+        let mut remaining = burned;
+        for tile in tileset.bypass_change_detection().tiles.values_mut() {
+            if remaining <= 0.0 {
+                break;
+            }
+            let take = tile.fuel().min(remaining);
+            if take > 0.0 {
+                tile.fuel = Some(tile.fuel() - take);
+                remaining -= take;
+            }
+        }
+
+        let thrust = wanted * burned / needed;
+        let world = (transform.rotation * thrust_direction(&tileset).extend(0.0)).truncate();
+
+        applied.force = world * thrust;
+        ext_force.force += applied.force;
+    }
+}
+
+/// Keeps each tank's collider mass in step with its fuel so Rapier sees the
+/// ship getting lighter.
+pub fn sync_tank_mass(
+    ships: Query<(&TileSet, &Children)>,
+    mut tiles: Query<(&TileMarker, &mut ColliderMassProperties)>,
+) {
+    for (tileset, children) in ships.iter() {
+        for &child in children.iter() {
+            let Ok((marker, mut mass_props)) = tiles.get_mut(child) else {
+                continue;
+            };
+            if marker.kind != TileKind::FuelTank {
+                continue;
+            }
+            let Some(tile) = tileset.tiles.get(&marker.pos) else {
+                continue;
+            };
+            let mass = tile.mass();
+            if *mass_props != ColliderMassProperties::Mass(mass) {
+                *mass_props = ColliderMassProperties::Mass(mass);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ship(fuel: Option<f32>) -> TileSet {
+        TileSet::from(vec![
+            Tile { pos: (0, 0), kind: TileKind::Engine, facing: Facing::Down, fuel: None, damaged: false },
+            Tile { pos: (0, 1), kind: TileKind::FuelTank, facing: Facing::Up, fuel: fuel, damaged: false },
+        ])
+    }
+
+    #[test]
+    fn delta_v_follows_the_rocket_equation() {
+        let dry = TileKind::Engine.dry_mass() + TileKind::FuelTank.dry_mass();
+        let exhaust = TileKind::Engine.isp() * STANDARD_GRAVITY;
+
+        for fuel in [TileKind::FuelTank.fuel_capacity(), 0.5] {
+            let expected = exhaust * ((dry + fuel) / dry).ln();
+            let actual = delta_v(&ship(Some(fuel)));
+            assert!((actual - expected).abs() < 1.0e-3, "{} fuel: {} vs {}", fuel, actual, expected);
+        }
+    }
+
+    #[test]
+    fn no_fuel_or_engines_means_no_delta_v() {
+        assert_eq!(delta_v(&ship(Some(0.0))), 0.0);
+        let hull = TileSet::from(vec![(0, 0)]);
+        assert_eq!(delta_v(&hull), 0.0);
+    }
+}
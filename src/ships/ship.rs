@@ -3,28 +3,83 @@ use std::f32::consts::*;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use super::tiles::TileSet;
-use crate::common::*;
-use crate::physics::gravity::Orbital;
+use super::autopilot::{Autopilot, ManeuverPlan};
+use super::control::ShipControl;
+use super::propulsion::AppliedThrust;
+use super::tiles::{Facing, Tile, TileKind, TileSet};
+use super::weapons::Armament;
+use crate::physics::gravity::{Orbital, PlaceInOrbit};
+use crate::physics::orbits::OrbitalElements;
 
 #[derive(Component)]
 pub struct Ship;
 
+/// The ship flown from the keyboard.
+#[derive(Component)]
+pub struct Player;
+
 pub fn make_ships_system(
     mut commands: Commands
 ) {
-    let orbit_speed = (G * 2500000000000000.0 / 50.0).sqrt();
-    info!("Orbit speed: {:?}", orbit_speed);
-    let component_speed = FRAC_PI_4.sin() * (orbit_speed + 5.0);
+    // Periapsis 50 out to the lower right, a little faster than circular
+    let player = spawn_ship_in_orbit(
+        &mut commands,
+        "Player",
+        TileSet::from(vec![
+            Tile::from((0, 1)),
+            Tile { kind: TileKind::Rcs, ..Tile::from((0, 0)) },
+            Tile { kind: TileKind::FuelTank, ..Tile::from((1, 1)) },
+            Tile { kind: TileKind::Engine, facing: Facing::Down, ..Tile::from((1, 0)) },
+            Tile { kind: TileKind::Gun, ..Tile::from((0, 2)) },
+            Tile { kind: TileKind::MissileLauncher, ..Tile::from((1, 2)) },
+        ]),
+        "Earth",
+        OrbitalElements {
+            semimajor: 61.02,
+            eccentricity: 0.1806,
+            argument: -FRAC_PI_4,
+            ..default()
+        },
+    );
+    commands.entity(player).insert(Player);
+
+    // commands.spawn_bundle((
+    //     Ship,
+    //     Name::new("Other"),
+    //     TileSet::from(vec![(0,0), (0, 1), (1, -1)]),
+    //     RigidBody::Dynamic,
+    //     Velocity {
+    //         angular: AxisAngle::new(Vec3::Y, 3.0),
+    //         ..default()
+    //     }
+    // )).insert_bundle(
+    //     TransformBundle {
+    //         local: Transform {
+    //             translation: Vec3::new(1.0, 2.5, 0.0),
+    //             ..default()
+    //         },
+    //         ..default()
+    //     });
+
+}
+
+pub fn spawn_ship(
+    commands: &mut Commands,
+    name: &str,
+    tileset: TileSet,
+    position: Vec2,
+    velocity: Vec2,
+) -> Entity {
     commands.spawn((
         Ship,
         Orbital,
-        Name::new("Player"),
-        TileSet::from(vec![(0, 1), (1, 1), (1, 0)]),
+        ShipControl::default(),
+        AppliedThrust::default(),
+        Name::new(name.to_string()),
+        tileset,
         RigidBody::Dynamic,
-        Mass { value: 1.0 },
         Velocity {
-            linvel: Vec2::new(component_speed, component_speed),
+            linvel: velocity,
             ..default()
         },
         ColliderMassProperties::Density(1.0),
@@ -38,29 +93,82 @@ pub fn make_ships_system(
         },
         SpatialBundle{
             transform: Transform {
-                translation: Vec3::new(35.35533905932737, -35.35533905932737, 0.0),
+                translation: position.extend(0.0),
                 ..default()
             },
             ..default()
         }
-    ));
+    )).insert((
+        Autopilot::default(),
+        ManeuverPlan::default(),
+        Armament::default(),
+    )).id()
+}
 
-    // commands.spawn_bundle((
-    //     Ship,
-    //     Name::new("Other"),
-    //     TileSet::from(vec![(0,0), (0, 1), (1, -1)]),
-    //     RigidBody::Dynamic,
-    //     Velocity {
-    //         angular: AxisAngle::new(Vec3::Y, 3.0),
-    //         ..default()
-    //     }
-    // )).insert_bundle(
-    //     TransformBundle {
-    //         local: Transform {
-    //             translation: Vec3::new(1.0, 2.5, 0.0),
-    //             ..default()
-    //         },
-    //         ..default()
-    //     });
+/// Spawns a ship on the orbit given by `elements` around the planet named
+/// `around`. It's placed once that planet exists.
+pub fn spawn_ship_in_orbit(
+    commands: &mut Commands,
+    name: &str,
+    tileset: TileSet,
+    around: &str,
+    elements: OrbitalElements,
+) -> Entity {
+    let ship = spawn_ship(commands, name, tileset, Vec2::ZERO, Vec2::ZERO);
+    commands.entity(ship).insert(PlaceInOrbit {
+        around: around.to_string(),
+        elements: elements,
+    });
+    ship
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::divergence::OrbitDiverged;
+    use crate::physics::gravity::{calc_orbits, place_in_orbit};
+    use crate::physics::orbits::Orbit;
+    use crate::planets::planet::{spawn_planet, Planet, PlanetMotion, PlanetSpec};
+
+    #[test]
+    fn ships_placed_in_orbit_fit_their_elements() {
+        let elements = OrbitalElements {
+            semimajor: 60.0,
+            eccentricity: 0.2,
+            argument: -0.8,
+            true_anomaly: 2.0,
+            clockwise: true,
+        };
+
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .add_event::<OrbitDiverged>()
+            .add_startup_system(move |mut commands: Commands| {
+                spawn_planet(&mut commands, &PlanetSpec {
+                    name: "Earth".to_string(),
+                    mass: 2.5e15,
+                    radius: 20.0,
+                    roughness: 0.0,
+                    rotation_period: 0.0,
+                    position: (30.0, -10.0),
+                    velocity: (0.0, 0.0),
+                    orbit: None,
+                    motion: PlanetMotion::Fixed,
+                });
+                spawn_ship_in_orbit(&mut commands, "Player", TileSet::from(Vec::<Tile>::new()), "Earth", elements);
+            })
+            .add_systems((place_in_orbit, calc_orbits).chain());
+        app.update();
 
+        let planet = app.world.query_filtered::<Entity, With<Planet>>().single(&app.world);
+        let orbit = app.world.query::<&Orbit>().single(&app.world);
+        assert_eq!(orbit.planet, planet);
+        assert_eq!(orbit.focus, Vec3::new(30.0, -10.0, 0.0));
+        assert!((orbit.semimajor - elements.semimajor).abs() < 1.0e-2, "semimajor {}", orbit.semimajor);
+        assert!((orbit.eccentricity - elements.eccentricity).abs() < 1.0e-4, "eccentricity {}", orbit.eccentricity);
+        assert!((orbit.argument - elements.argument).abs() < 1.0e-4, "argument {}", orbit.argument);
+        assert!((orbit.initial_true_anomaly - elements.true_anomaly).abs() < 1.0e-4, "true anomaly {}", orbit.initial_true_anomaly);
+        assert!(orbit.clockwise);
+    }
 }
@@ -0,0 +1,74 @@
+//! A ship's target, any other ship or planet, and its motion relative to it
+//! for docking and intercepts.
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::physics::orbits::*;
+
+/// Closest approach is looked for at most this far ahead, or one orbit if sooner.
+const APPROACH_HORIZON: f32 = 300.0;
+
+#[derive(Component)]
+pub struct Target {
+    pub entity: Entity,
+}
+
+/// How the ship is moving relative to its `Target`, updated every frame.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct TargetInfo {
+    pub distance: f32,
+    /// The ship's velocity minus the target's
+    pub relative_velocity: Vec2,
+    /// Positive while the distance is shrinking
+    pub closing_speed: f32,
+    /// Seconds on the simulation clock and distance, when both are on
+    /// closed orbits around the same body or the target is that body
+    pub closest_approach: Option<(f32, f32)>,
+}
+
+pub fn update_target_info(
+    mut commands: Commands,
+    time: Res<Time>,
+    bodies: Query<(&GlobalTransform, &Velocity, Option<&Orbit>)>,
+    mut ships: Query<(Entity, &Name, &Target, Option<&mut TargetInfo>)>,
+) {
+    let now = time.elapsed_seconds();
+
+    for (ship, name, target, info) in ships.iter_mut() {
+        let (Ok((transform, velocity, orbit)), Ok((target_transform, target_velocity, target_orbit))) = (bodies.get(ship), bodies.get(target.entity)) else {
+            info!("{}: target lost", name);
+            commands.entity(ship).remove::<(Target, TargetInfo)>();
+            continue;
+        };
+
+        let offset = (target_transform.translation() - transform.translation()).truncate();
+        let relative_velocity = velocity.linvel - target_velocity.linvel;
+        // Hyperbolic orbits have no period and `world_position_at` gives NaN on them
+        let closed = |orbit: &&Orbit| orbit.eccentricity < 1.0;
+        let closest_approach = orbit.filter(closed).and_then(|orbit| {
+            let horizon = orbit.period.min(APPROACH_HORIZON);
+            if orbit.planet == target.entity {
+                Some(closest_approach(orbit, None, now, horizon))
+            } else {
+                target_orbit
+                    .filter(closed)
+                    .filter(|target_orbit| target_orbit.planet == orbit.planet)
+                    .map(|target_orbit| closest_approach(orbit, Some(target_orbit), now, horizon))
+            }
+        });
+
+        let updated = TargetInfo {
+            distance: offset.length(),
+            relative_velocity: relative_velocity,
+            closing_speed: relative_velocity.dot(offset.normalize_or_zero()),
+            closest_approach: closest_approach,
+        };
+        match info {
+            Some(mut info) => *info = updated,
+            None => {
+                commands.entity(ship).insert(updated);
+            },
+        }
+    }
+}
@@ -1,62 +1,176 @@
 use std::collections::BTreeMap;
 
-use bevy::{
-    prelude::*,
-    prelude::shape,
-    reflect::TypeUuid,
-};
+use bevy::prelude::*;
 
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
-type Pos = (i32, i32);
+use super::collisions::CONTACT_FORCE_THRESHOLD;
+
+pub type Pos = (i32, i32);
 
 #[derive(Component)]
-pub struct TileMarker;
+pub struct TileMarker {
+    pub pos: Pos,
+    pub kind: TileKind,
+}
 
-#[derive(TypeUuid)]
-#[uuid = "3af77720-190d-42f4-a65c-bede1fb4b01a"]
-pub struct TileParts {
-    mesh: Handle<Mesh>,
-    materials: BTreeMap<&'static str, Handle<StandardMaterial>>
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileKind {
+    #[default]
+    Hull,
+    DockingPort,
+    FuelTank,
+    /// Pushes opposite to the direction it faces.
+    Engine,
+    /// Attitude thrusters, adding turning torque.
+    Rcs,
+    /// Fires kinetic rounds the way it faces.
+    Gun,
+    /// Launches missiles that home on the ship's target.
+    MissileLauncher,
 }
 
-impl FromWorld for TileParts {
-    fn from_world(world: &mut World) -> Self {
-        let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
-        let mesh = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(1.0, 1.0))));
-
-        let mut materials = world.get_resource_mut::<Assets<StandardMaterial>>().unwrap();
-        let mut mats = BTreeMap::new();
-        mats.insert("gray", materials.add(StandardMaterial {
-            base_color: Color::hex("999999").unwrap(),
-            unlit: true,
-            ..default()
-        }));
-
-        TileParts{
-            mesh: mesh,
-            materials: mats,
+impl TileKind {
+    pub fn dry_mass(self) -> f32 {
+        match self {
+            TileKind::Hull => 1.0,
+            TileKind::DockingPort => 1.0,
+            TileKind::FuelTank => 0.5,
+            TileKind::Engine => 1.5,
+            TileKind::Rcs => 0.75,
+            TileKind::Gun => 1.25,
+            TileKind::MissileLauncher => 1.5,
+        }
+    }
+
+    pub fn fuel_capacity(self) -> f32 {
+        match self {
+            TileKind::FuelTank => 2.0,
+            _ => 0.0,
+        }
+    }
+
+    pub fn thrust(self) -> f32 {
+        match self {
+            TileKind::Engine => 20.0,
+            _ => 0.0,
+        }
+    }
+
+    pub fn torque(self) -> f32 {
+        match self {
+            TileKind::Rcs => 6.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Specific impulse in seconds.
+    pub fn isp(self) -> f32 {
+        match self {
+            TileKind::Engine => 8.0,
+            _ => 0.0,
         }
     }
 }
 
-#[derive(Clone)]
+/// Which grid edge a tile faces, in its ship's frame.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Facing {
+    #[default]
+    Up,
+    Left,
+    Down,
+    Right,
+}
+
+impl Facing {
+    const CCW: [Facing; 4] = [Facing::Up, Facing::Left, Facing::Down, Facing::Right];
+
+    pub fn vector(self) -> IVec2 {
+        match self {
+            Facing::Up => IVec2::new(0, 1),
+            Facing::Left => IVec2::new(-1, 0),
+            Facing::Down => IVec2::new(0, -1),
+            Facing::Right => IVec2::new(1, 0),
+        }
+    }
+
+    /// Counter-clockwise quarter turns from `Up`.
+    pub fn quarter_turns(self) -> i32 {
+        Facing::CCW.iter().position(|&f| f == self).unwrap() as i32
+    }
+
+    /// Rotate by `quarter_turns` counter-clockwise.
+    pub fn rotated(self, quarter_turns: i32) -> Facing {
+        Facing::CCW[(self.quarter_turns() + quarter_turns).rem_euclid(4) as usize]
+    }
+}
+
+/// Rotate a grid position by `quarter_turns` counter-clockwise about the origin.
+pub fn rotate_quarter(pos: IVec2, quarter_turns: i32) -> IVec2 {
+    (0..quarter_turns.rem_euclid(4)).fold(pos, |p, _| IVec2::new(-p.y, p.x))
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct Tile {
-    pos: Pos
+    pub pos: Pos,
+    #[serde(default)]
+    pub kind: TileKind,
+    #[serde(default)]
+    pub facing: Facing,
+    /// Propellant mass for tanks; `None` means full.
+    #[serde(default)]
+    pub fuel: Option<f32>,
+    #[serde(default)]
+    pub damaged: bool,
+}
+
+impl Tile {
+    pub fn fuel(&self) -> f32 {
+        self.fuel.unwrap_or(self.kind.fuel_capacity())
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.kind.dry_mass() + self.fuel()
+    }
 }
 
 impl From<Pos> for Tile {
     fn from(pos: Pos) -> Self {
-        Tile{ pos }
+        Tile{ pos, kind: TileKind::Hull, facing: Facing::Up, fuel: None, damaged: false }
     }
 }
 
+/// A docking port tile on some ship, facing out of its grid.
+#[derive(Component)]
+pub struct DockingPort {
+    pub facing: Facing,
+}
+
 
 #[derive(Component)]
 pub struct TileSet {
     pub tiles: BTreeMap<Pos, Tile>,
 }
 
+impl TileSet {
+    /// Damages the tile at `pos`, or removes it if it was already damaged or
+    /// `destroy` is set. Returns whether the tile was removed.
+    pub fn damage(&mut self, pos: Pos, destroy: bool) -> bool {
+        match self.tiles.get_mut(&pos) {
+            Some(tile) if !tile.damaged && !destroy => {
+                tile.damaged = true;
+                false
+            },
+            Some(_) => {
+                self.tiles.remove(&pos);
+                true
+            },
+            None => false,
+        }
+    }
+}
 
 impl From<Vec<Tile>> for TileSet {
     fn from(tiles: Vec<Tile>) -> Self {
@@ -76,34 +190,44 @@ impl From<Vec<Pos>> for TileSet {
 }
 
 
+/// (Re)builds a ship's tile colliders whenever its `TileSet` changes, so
+/// docking and damage can edit the set in place.
 pub fn make_tiles_system(
     mut commands: Commands,
-    mut query: Query<(Entity, &TileSet), Added<TileSet>>,
-    tile_parts: Local<TileParts>,
+    query: Query<(Entity, &TileSet, Option<&Children>), Changed<TileSet>>,
+    existing: Query<(), With<TileMarker>>,
 ) {
-    for (ship, tileset) in query.iter_mut() {
+    for (ship, tileset, children) in query.iter() {
+        if let Some(children) = children {
+            for &child in children.iter() {
+                if existing.contains(child) {
+                    commands.entity(child).despawn_recursive();
+                }
+            }
+        }
+
         for tile in tileset.tiles.values() {
 
             let (x, y) = tile.pos;
 
-            let tile = commands.spawn((
-                TileMarker,
+            let mut tile_entity = commands.spawn((
+                TileMarker { pos: tile.pos, kind: tile.kind },
                 Collider::cuboid(0.5, 0.5),
+                ColliderMassProperties::Mass(tile.mass()),
+                ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS,
+                ContactForceEventThreshold(CONTACT_FORCE_THRESHOLD),
                 SpatialBundle {
                     transform: Transform::from_xyz(x as f32, y as f32, 0.0),
                     ..default()
                 }
-            )).with_children(|parent| {
-                parent.spawn(
-                    PbrBundle {
-                        mesh: tile_parts.mesh.clone(),
-                        material: tile_parts.materials.get("gray").unwrap().clone(),
-                        ..default()
-                    }
-                );
-            }).id();
-
-            commands.entity(ship).add_child(tile);
+            ));
+
+            if tile.kind == TileKind::DockingPort {
+                tile_entity.insert(DockingPort { facing: tile.facing });
+            }
+
+            let tile_entity = tile_entity.id();
+            commands.entity(ship).add_child(tile_entity);
         }
     }
 }
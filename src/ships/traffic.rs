@@ -0,0 +1,164 @@
+//! NPC ships on random orbits around a body, despawned once they crash or
+//! wander off.
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use super::landing::ShipCrashed;
+use super::ship::spawn_ship;
+use super::tiles::*;
+use crate::common::Mass;
+use crate::physics::orbits::*;
+use crate::planets::planet::Planet;
+use crate::planets::terrain::Terrain;
+
+/// Lowest periapsis above the highest terrain.
+const SURFACE_CLEARANCE: f32 = 3.0;
+/// Samples tried per ship before giving up on the element ranges.
+const MAX_ATTEMPTS: usize = 50;
+
+/// A batch of NPCs around `body`, the `Name` of a planet. Also the scenario
+/// file format.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrafficSpec {
+    pub body: String,
+    pub count: usize,
+    pub semimajor: (f32, f32),
+    #[serde(default)]
+    pub eccentricity: (f32, f32),
+    #[serde(default)]
+    pub seed: u64,
+    /// Tile layouts to pick from; a few stock ones if empty
+    #[serde(default)]
+    pub blueprints: Vec<Vec<Tile>>,
+    /// NPCs further than this from the body are despawned; four times the
+    /// largest semi-major axis if unset
+    #[serde(default)]
+    pub escape_radius: Option<f32>,
+}
+
+/// Spawns its traffic once the body exists, then goes away.
+#[derive(Component)]
+pub struct TrafficSpawner {
+    pub spec: TrafficSpec,
+}
+
+#[derive(Component)]
+pub struct Npc {
+    pub home: Entity,
+    pub escape_radius: f32,
+}
+
+pub fn stock_blueprints() -> Vec<Vec<Tile>> {
+    let engine = |pos| Tile { kind: TileKind::Engine, facing: Facing::Down, ..Tile::from(pos) };
+    let tank = |pos| Tile { kind: TileKind::FuelTank, ..Tile::from(pos) };
+    vec![
+        vec![Tile::from((0, 1)), engine((0, 0))],
+        vec![Tile::from((0, 2)), tank((0, 1)), engine((0, 0))],
+        vec![Tile::from((0, 1)), Tile::from((1, 1)), tank((0, 0)), engine((1, 0))],
+        vec![
+            Tile { kind: TileKind::DockingPort, ..Tile::from((0, 2)) },
+            tank((0, 1)),
+            Tile { kind: TileKind::Rcs, ..Tile::from((1, 1)) },
+            engine((0, 0)),
+        ],
+    ]
+}
+
+pub fn spawn_traffic(
+    mut commands: Commands,
+    spawners: Query<(Entity, &TrafficSpawner)>,
+    planets: Query<(Entity, &Name, &Transform, &Velocity, &Mass, &Terrain, Option<&Orbit>), With<Planet>>,
+    masses: Query<&Mass>,
+) {
+    for (spawner, TrafficSpawner { spec }) in spawners.iter() {
+        let Some((body, _, transform, velocity, mass, terrain, orbit)) = planets.iter()
+            .find(|(_, name, _, _, _, _, _)| name.as_str() == spec.body) else {
+            continue;
+        };
+        commands.entity(spawner).despawn();
+
+        let lowest = terrain.heights.iter().copied().fold(0.0, f32::max) + SURFACE_CLEARANCE;
+        let highest = orbit
+            .and_then(|orbit| {
+                let primary = masses.get(orbit.planet).ok()?;
+                Some(sphere_of_influence(orbit.semimajor, mass.value, primary.value))
+            })
+            .unwrap_or(f32::INFINITY);
+
+        let blueprints = if spec.blueprints.is_empty() { stock_blueprints() } else { spec.blueprints.clone() };
+        let escape_radius = spec.escape_radius.unwrap_or(spec.semimajor.1 * 4.0);
+        let mut rng = StdRng::seed_from_u64(spec.seed);
+        let mut spawned = 0;
+
+        for i in 0..spec.count {
+            // Reject orbits that hit the surface or leave the sphere of influence
+            let elements = (0..MAX_ATTEMPTS).map(|_| {
+                let semimajor = rng.gen_range(spec.semimajor.0..=spec.semimajor.1);
+                let eccentricity = rng.gen_range(spec.eccentricity.0..=spec.eccentricity.1);
+                (semimajor, eccentricity)
+            }).find(|(a, e)| a * (1.0 - e) > lowest && a * (1.0 + e) < highest);
+
+            let Some((semimajor, eccentricity)) = elements else {
+                warn!("Traffic around {}: no valid orbit in the given ranges", spec.body);
+                break;
+            };
+
+            let elements = OrbitalElements {
+                semimajor: semimajor,
+                eccentricity: eccentricity,
+                argument: rng.gen_range(0.0..std::f32::consts::TAU),
+                true_anomaly: rng.gen_range(0.0..std::f32::consts::TAU),
+                clockwise: false,
+            };
+            // Placed directly rather than with `PlaceInOrbit`, which would
+            // only catch up a frame later
+            let (r, v) = elements.state(mass.value);
+
+            let tiles = blueprints[rng.gen_range(0..blueprints.len())].clone();
+            let ship = spawn_ship(
+                &mut commands,
+                &format!("{} traffic {}", spec.body, i + 1),
+                TileSet::from(tiles),
+                transform.translation.truncate() + r.truncate(),
+                velocity.linvel + v.truncate(),
+            );
+            commands.entity(ship).insert(Npc {
+                home: body,
+                escape_radius: escape_radius,
+            });
+            spawned += 1;
+        }
+
+        info!("Spawned {} NPCs around {}", spawned, spec.body);
+    }
+}
+
+pub fn despawn_lost_npcs(
+    mut commands: Commands,
+    mut crashes: EventReader<ShipCrashed>,
+    planets: Query<&GlobalTransform, With<Planet>>,
+    npcs: Query<(Entity, &Name, &Npc, &GlobalTransform, &TileSet)>,
+) {
+    let crashed: Vec<Entity> = crashes.iter().map(|crash| crash.ship).collect();
+
+    for (ship, name, npc, transform, tileset) in npcs.iter() {
+        // Wrecks with nothing left are already being despawned
+        if tileset.tiles.is_empty() {
+            continue;
+        }
+        let escaped = planets.get(npc.home).map_or(true, |home| {
+            home.translation().distance(transform.translation()) > npc.escape_radius
+        });
+        if crashed.contains(&ship) {
+            info!("{} crashed, despawning", name);
+        } else if escaped {
+            info!("{} escaped, despawning", name);
+        } else {
+            continue;
+        }
+        commands.entity(ship).despawn_recursive();
+    }
+}
@@ -0,0 +1,171 @@
+//! Plans transfers to another body's orbit as `ManeuverNode`s, from the
+//! solvers in `physics::orbits`.
+
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, utils::Duration};
+
+use super::autopilot::{ManeuverNode, ManeuverPlan};
+use super::ship::Ship;
+use crate::common::Mass;
+use crate::physics::orbits::*;
+
+/// Nodes closer than this are pushed back a window, to leave time to turn.
+const NODE_LEAD: f32 = 5.0;
+/// Bi-elliptic transfers go out to this many times the larger radius.
+const BI_ELLIPTIC_APOAPSIS: f32 = 3.0;
+const PORKCHOP_DEPARTURES: usize = 16;
+const PORKCHOP_FLIGHT_TIMES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferKind {
+    /// Hohmann or bi-elliptic, whichever is cheaper, timed to meet the target
+    Coplanar,
+    /// The cheapest Lambert intercept on a porkchop table, which is logged
+    Intercept,
+}
+
+/// Asks for `ship`'s maneuver plan to be replaced with a transfer to `target`.
+pub struct TransferRequest {
+    pub ship: Entity,
+    pub target: Entity,
+    pub kind: TransferKind,
+}
+
+/// Current angle round the primary, measured in the orbit's direction of travel.
+fn travel_angle(orbit: &Orbit, time: f32) -> f32 {
+    let r = world_position_at(orbit, time) - orbit.focus;
+    let angle = r.y.atan2(r.x);
+    if orbit.clockwise { -angle } else { angle }
+}
+
+fn mean_motion(orbit: &Orbit) -> f32 {
+    TAU / orbit.period
+}
+
+/// Seconds from `time` until `target` leads the ship by `phase`, at least
+/// `NODE_LEAD` away. Treats both orbits as circular.
+fn phase_wait(orbit: &Orbit, target: &Orbit, time: f32, phase: f32) -> Option<f32> {
+    let rate = mean_motion(target) - mean_motion(orbit);
+    if rate.abs() < f32::EPSILON {
+        return None;
+    }
+    let lead = travel_angle(target, time) - travel_angle(orbit, time);
+    let wait = if rate > 0.0 {
+        (phase - lead).rem_euclid(TAU) / rate
+    } else {
+        (lead - phase).rem_euclid(TAU) / -rate
+    };
+    let synodic = TAU / rate.abs();
+    Some(if wait < NODE_LEAD { wait + synodic } else { wait })
+}
+
+/// The cheaper of a Hohmann and a bi-elliptic transfer out to `target`'s
+/// orbit, leaving when the target will be there to meet the ship.
+pub fn coplanar_nodes(orbit: &Orbit, target: &Orbit, primary_mass: f32, time: f32) -> Option<Vec<ManeuverNode>> {
+    if orbit.planet != target.planet || orbit.clockwise != target.clockwise
+        || !orbit.period.is_finite() || !target.period.is_finite() {
+        return None;
+    }
+
+    let (r1, r2) = (orbit.semimajor, target.semimajor);
+    let hohmann = hohmann_transfer(primary_mass, r1, r2);
+    let bi_elliptic = bi_elliptic_transfer(primary_mass, r1, r2, r1.max(r2) * BI_ELLIPTIC_APOAPSIS);
+    let transfer = if bi_elliptic.delta_v() < hohmann.delta_v() { bi_elliptic } else { hohmann };
+
+    // Where the target has to be now, relative to the ship, to arrive together
+    let phase = transfer.sweep - mean_motion(target) * transfer.duration();
+    let departure = time + phase_wait(orbit, target, time, phase)?;
+
+    Some(transfer.burns.iter().map(|&(offset, delta_v)| ManeuverNode {
+        time: departure + offset,
+        prograde: delta_v,
+        radial: 0.0,
+    }).collect())
+}
+
+/// Departures over one synodic period, capped at a few orbits, against flight
+/// times either side of the Hohmann transfer time.
+pub fn porkchop_for(orbit: &Orbit, target: &Orbit, primary_mass: f32, time: f32) -> Porkchop {
+    let rate = (mean_motion(target) - mean_motion(orbit)).abs();
+    let span = if rate > f32::EPSILON { (TAU / rate).min(orbit.period * 4.0) } else { orbit.period };
+    let hohmann = hohmann_transfer(primary_mass, orbit.semimajor, target.semimajor).duration();
+
+    let departures = (0..PORKCHOP_DEPARTURES)
+        .map(|i| time + NODE_LEAD + span * i as f32 / PORKCHOP_DEPARTURES as f32)
+        .collect();
+    let flight_times = (0..PORKCHOP_FLIGHT_TIMES)
+        .map(|i| hohmann * (0.3 + 1.2 * i as f32 / (PORKCHOP_FLIGHT_TIMES - 1) as f32))
+        .collect();
+    Porkchop::new(orbit, target, primary_mass, departures, flight_times)
+}
+
+/// A Lambert intercept of `target`, plus a burn to match its velocity on
+/// arrival if `rendezvous`.
+pub fn intercept_nodes(
+    orbit: &Orbit,
+    target: &Orbit,
+    primary_mass: f32,
+    departure: f32,
+    flight_time: f32,
+    rendezvous: bool,
+) -> Option<Vec<ManeuverNode>> {
+    let (first, second) = intercept(orbit, target, primary_mass, departure, flight_time)?;
+    let mut nodes = vec![ManeuverNode::from_world(orbit, departure, first.truncate())];
+
+    if rendezvous {
+        // The arrival burn is flown from the transfer orbit, so describe it there
+        let r = world_position_at(orbit, departure) - orbit.focus;
+        let v = velocity_at(orbit, departure) + first;
+        let transfer = orbit_from_initial(r, v, primary_mass, orbit.planet, orbit.focus, Duration::from_secs_f32(departure));
+        nodes.push(ManeuverNode::from_world(&transfer, departure + flight_time, second.truncate()));
+    }
+    Some(nodes)
+}
+
+pub fn plan_transfers(
+    time: Res<Time>,
+    mut requests: EventReader<TransferRequest>,
+    masses: Query<&Mass>,
+    targets: Query<(&Name, &Orbit, Option<&Ship>)>,
+    mut ships: Query<(&Name, &Orbit, &mut ManeuverPlan)>,
+) {
+    let now = time.elapsed_seconds();
+
+    for request in requests.iter() {
+        let (Ok((name, orbit, mut plan)), Ok((target_name, target, target_ship))) = (ships.get_mut(request.ship), targets.get(request.target)) else {
+            continue;
+        };
+        if target.planet != orbit.planet {
+            warn!("{}: {} isn't orbiting the same body", name, target_name);
+            continue;
+        }
+        let Ok(primary) = masses.get(orbit.planet) else {
+            continue;
+        };
+
+        let nodes = match request.kind {
+            TransferKind::Coplanar => coplanar_nodes(orbit, target, primary.value, now),
+            TransferKind::Intercept => {
+                let porkchop = porkchop_for(orbit, target, primary.value, now);
+                info!("{} to {}, delta-v by departure and flight time:\n{}", name, target_name, porkchop.table(now));
+                porkchop.best().and_then(|(departure, flight_time, _)| {
+                    intercept_nodes(orbit, target, primary.value, departure, flight_time, target_ship.is_some())
+                })
+            },
+        };
+
+        match nodes {
+            Some(nodes) => {
+                let delta_v: f32 = nodes.iter().map(|node| node.delta_v()).sum();
+                info!("{}: {:?} transfer to {} in {:.1}s, {} burns, dv {:.2}",
+                      name, request.kind, target_name, nodes[0].time - now, nodes.len(), delta_v);
+                plan.nodes.clear();
+                for node in nodes {
+                    plan.add(node);
+                }
+            },
+            None => warn!("{}: no {:?} transfer to {}", name, request.kind, target_name),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Gun and missile tiles. Their projectiles are `Orbital`, so gravity bends
+//! them and they get orbit paths like anything else, and they damage the
+//! tiles they hit.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::collisions::{damage_tiles, TileDestroyed};
+use super::control::ShipControl;
+use super::ship::Ship;
+use super::targeting::Target;
+use super::tiles::*;
+use crate::common::Mass;
+use crate::physics::gravity::Orbital;
+use crate::physics::orbits::*;
+use crate::planets::planet::Planet;
+
+/// Projectiles can't hit the ship that fired them until this old.
+const ARMING_TIME: f32 = 0.5;
+/// Muzzles sit this far out from the weapon tile's centre.
+const MUZZLE_OFFSET: f32 = 0.8;
+const PROJECTILE_RADIUS: f32 = 0.15;
+/// Lead solutions look for an intercept this many seconds out, in steps.
+const LEAD_HORIZON: f32 = 20.0;
+const LEAD_SAMPLES: usize = 40;
+const LEAD_REFINEMENTS: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WeaponStats {
+    /// Relative to the tile
+    pub muzzle_speed: f32,
+    /// Seconds between shots
+    pub reload: f32,
+    /// Seconds before an unspent projectile is removed
+    pub lifetime: f32,
+    pub mass: f32,
+    /// Hits remove tiles outright instead of damaging them first
+    pub destroys: bool,
+    /// Homing thrust and how many seconds it lasts; zero for unguided rounds
+    pub thrust: f32,
+    pub burn_time: f32,
+}
+
+impl TileKind {
+    pub fn weapon(self) -> Option<WeaponStats> {
+        match self {
+            TileKind::Gun => Some(WeaponStats {
+                muzzle_speed: 30.0,
+                reload: 0.4,
+                lifetime: 60.0,
+                mass: 0.05,
+                destroys: false,
+                thrust: 0.0,
+                burn_time: 0.0,
+            }),
+            TileKind::MissileLauncher => Some(WeaponStats {
+                muzzle_speed: 4.0,
+                reload: 5.0,
+                lifetime: 90.0,
+                mass: 0.3,
+                destroys: true,
+                thrust: 1.2,
+                burn_time: 20.0,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Time until each weapon tile can fire again, by position.
+#[derive(Component, Default)]
+pub struct Armament {
+    pub reloading: BTreeMap<Pos, f32>,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub shooter: Entity,
+    pub stats: WeaponStats,
+    pub age: f32,
+}
+
+/// Steers a missile at `target` while it has fuel.
+#[derive(Component)]
+pub struct Guidance {
+    pub target: Entity,
+}
+
+/// Where to point a gun to hit the target: the direction in world space, how
+/// long the round takes, and where they meet.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct LeadSolution {
+    pub direction: Vec2,
+    pub flight_time: f32,
+    pub point: Vec3,
+}
+
+/// Aims a round of `muzzle_speed` from a ship on `orbit` at one on `target`
+/// around the same primary: the flight time whose Lambert arc needs exactly
+/// the muzzle speed on top of the ship's own velocity.
+pub fn lead_solution(orbit: &Orbit, target: &Orbit, primary_mass: f32, muzzle_speed: f32, time: f32) -> Option<LeadSolution> {
+    let r1 = world_position_at(orbit, time) - orbit.focus;
+    let v1 = velocity_at(orbit, time);
+    let shot = |flight_time: f32| {
+        let r2 = world_position_at(target, time + flight_time) - target.focus;
+        let (departure, _) = lambert(primary_mass, r1, r2, flight_time, orbit.clockwise)?;
+        Some(departure - v1)
+    };
+    // Short flights need more than the muzzle speed, so the first flight
+    // time that needs less brackets the intercept
+    let excess = |flight_time: f32| shot(flight_time).map(|dv| dv.length() - muzzle_speed);
+
+    let step = LEAD_HORIZON / LEAD_SAMPLES as f32;
+    let samples: Vec<(f32, Option<f32>)> = (1..=LEAD_SAMPLES)
+        .map(|i| (i as f32 * step, excess(i as f32 * step)))
+        .collect();
+    let (mut low, mut high) = samples.windows(2).find_map(|pair| match (pair[0], pair[1]) {
+        ((low, Some(a)), (high, Some(b))) if a > 0.0 && b <= 0.0 => Some((low, high)),
+        _ => None,
+    })?;
+    for _ in 0..LEAD_REFINEMENTS {
+        let mid = (low + high) / 2.0;
+        if excess(mid).map_or(true, |e| e > 0.0) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    let flight_time = high;
+    Some(LeadSolution {
+        direction: shot(flight_time)?.truncate().normalize_or_zero(),
+        flight_time: flight_time,
+        point: world_position_at(target, time + flight_time),
+    })
+}
+
+/// Fires every reloaded, intact weapon whose trigger is held, kicking the
+/// ship back by the projectile's momentum.
+pub fn fire_weapons(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut ships: Query<(Entity, &ShipControl, &TileSet, &GlobalTransform, &Velocity, &mut Armament, Option<&Target>)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (ship, control, tileset, transform, velocity, mut armament, target) in ships.iter_mut() {
+        for reload in armament.reloading.values_mut() {
+            *reload -= dt;
+        }
+        armament.reloading.retain(|_, reload| *reload > 0.0);
+
+        let mut recoil = Vec2::ZERO;
+        for tile in tileset.tiles.values() {
+            let Some(stats) = tile.kind.weapon() else {
+                continue;
+            };
+            let trigger = if stats.thrust > 0.0 { control.fire_missiles } else { control.fire_guns };
+            if !trigger || tile.damaged || armament.reloading.contains_key(&tile.pos) {
+                continue;
+            }
+            armament.reloading.insert(tile.pos, stats.reload);
+
+            let (_, rotation, centre) = transform.to_scale_rotation_translation();
+            let facing = (rotation * tile.facing.vector().as_vec2().extend(0.0)).truncate();
+            let local = Vec3::new(tile.pos.0 as f32, tile.pos.1 as f32, 0.0);
+            let muzzle = transform.transform_point(local) + facing.extend(0.0) * MUZZLE_OFFSET;
+            // The tile's own velocity, spin included
+            let base = velocity.linvel + velocity.angvel * (muzzle - centre).truncate().perp();
+
+            let mut projectile = commands.spawn((
+                Projectile {
+                    shooter: ship,
+                    stats: stats,
+                    age: 0.0,
+                },
+                Name::new(format!("{:?} round", tile.kind)),
+                Orbital,
+                RigidBody::Dynamic,
+                Collider::ball(PROJECTILE_RADIUS),
+                ColliderMassProperties::Mass(stats.mass),
+                ActiveEvents::COLLISION_EVENTS,
+                Ccd::enabled(),
+                Velocity {
+                    linvel: base + facing * stats.muzzle_speed,
+                    ..default()
+                },
+                SpatialBundle {
+                    transform: Transform {
+                        translation: muzzle,
+                        rotation: rotation,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+            if let (true, Some(target)) = (stats.thrust > 0.0, target) {
+                projectile.insert(Guidance { target: target.entity });
+            }
+            recoil -= facing * stats.muzzle_speed * stats.mass;
+        }
+
+        if recoil != Vec2::ZERO {
+            commands.entity(ship).insert(ExternalImpulse {
+                impulse: recoil,
+                torque_impulse: 0.0,
+            });
+        }
+    }
+}
+
+/// Pursuit guidance: thrust to cancel the velocity across the line of sight
+/// while closing. Runs after gravity has been written.
+pub fn guide_missiles(
+    targets: Query<(&GlobalTransform, &Velocity), Without<Guidance>>,
+    mut missiles: Query<(&Projectile, &Guidance, &GlobalTransform, &Velocity, &mut ExternalForce, &mut Transform)>,
+) {
+    for (projectile, guidance, global, velocity, mut force, mut transform) in missiles.iter_mut() {
+        if projectile.age > projectile.stats.burn_time {
+            continue;
+        }
+        let Ok((target_transform, target_velocity)) = targets.get(guidance.target) else {
+            continue;
+        };
+
+        let line_of_sight = (target_transform.translation() - global.translation()).truncate().normalize_or_zero();
+        let relative = velocity.linvel - target_velocity.linvel;
+        let across = relative - line_of_sight * relative.dot(line_of_sight);
+        let direction = (line_of_sight - across * 0.5).normalize_or_zero();
+
+        force.force += direction * projectile.stats.thrust;
+        transform.rotation = Quat::from_rotation_z(Vec2::Y.angle_between(direction));
+    }
+}
+
+pub fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectiles: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in projectiles.iter_mut() {
+        projectile.age += time.delta_seconds();
+        if projectile.age > projectile.stats.lifetime {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Projectiles damage the tile they hit, or vanish into a planet.
+pub fn detect_hits(
+    mut commands: Commands,
+    mut events: EventReader<CollisionEvent>,
+    mut destroyed: EventWriter<TileDestroyed>,
+    projectiles: Query<&Projectile>,
+    tiles: Query<(&TileMarker, &Parent)>,
+    planets: Query<(), With<Planet>>,
+    mut ships: Query<(&Name, &GlobalTransform, &mut TileSet), With<Ship>>,
+) {
+    let mut spent: Vec<Entity> = Vec::new();
+
+    for event in events.iter() {
+        let CollisionEvent::Started(a, b, _) = event else {
+            continue;
+        };
+        for (round, other) in [(*a, *b), (*b, *a)] {
+            let Ok(projectile) = projectiles.get(round) else {
+                continue;
+            };
+            if spent.contains(&round) {
+                continue;
+            }
+
+            if let Ok((marker, parent)) = tiles.get(other) {
+                let ship = parent.get();
+                if ship == projectile.shooter && projectile.age < ARMING_TIME {
+                    continue;
+                }
+                if let Ok((name, transform, mut tileset)) = ships.get_mut(ship) {
+                    info!("{} hit at {:?}", name, marker.pos);
+                    damage_tiles(ship, transform, &mut tileset, &[marker.pos], projectile.stats.destroys, &mut destroyed);
+                    if tileset.tiles.is_empty() {
+                        commands.entity(ship).despawn_recursive();
+                    }
+                }
+            } else if !planets.contains(other) && !projectiles.contains(other) {
+                continue;
+            }
+
+            spent.push(round);
+            commands.entity(round).despawn_recursive();
+        }
+    }
+}
+
+/// Keeps a lead solution on every armed ship with a target sharing its
+/// primary, for its first gun. Each one is a Lambert search, so this runs on
+/// a timer rather than every frame.
+pub fn update_lead_solutions(
+    mut commands: Commands,
+    time: Res<Time>,
+    masses: Query<&Mass>,
+    orbits: Query<&Orbit>,
+    mut ships: Query<(Entity, &TileSet, &Orbit, Option<&Target>, Option<&mut LeadSolution>), With<Armament>>,
+) {
+    for (ship, tileset, orbit, target, current) in ships.iter_mut() {
+        let gun = tileset.tiles.values()
+            .filter_map(|tile| tile.kind.weapon())
+            .find(|stats| stats.thrust <= 0.0);
+
+        let solution = gun.zip(target)
+            .and_then(|(gun, target)| Some((gun, orbits.get(target.entity).ok()?)))
+            .filter(|(_, target)| target.planet == orbit.planet)
+            .and_then(|(gun, target)| {
+                let primary = masses.get(orbit.planet).ok()?;
+                lead_solution(orbit, target, primary.value, gun.muzzle_speed, time.elapsed_seconds())
+            });
+
+        match (solution, current) {
+            (Some(solution), Some(mut current)) => *current = solution,
+            (Some(solution), None) => {
+                commands.entity(ship).insert(solution);
+            },
+            (None, Some(_)) => {
+                commands.entity(ship).remove::<LeadSolution>();
+            },
+            (None, None) => {},
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use bevy::{
+    app::PluginGroupBuilder,
+    prelude::*,
+    time::TimeUpdateStrategy,
+    utils::Duration,
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::audio::{AudioBackend, SparkAudioPlugin};
+use crate::physics::{SparkOrbitsPlugin, SparkPhysicsPlugin};
+use crate::physics::trajectory::TrajectoryLog;
+use crate::planets::SparkPlanetsPlugin;
+use crate::scenario::{spawn_scenario_system, Scenario};
+use crate::ships::SparkShipsPlugin;
+
+/// The renderer-free core of the game: Rapier, tile colliders, gravity and
+/// orbit fitting. Runs under either `DefaultPlugins` or `MinimalPlugins`.
+pub struct SimulationPlugins;
+
+impl PluginGroup for SimulationPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(SparkPhysicsPlugin)
+            .add(SparkOrbitsPlugin)
+            .add(SparkShipsPlugin)
+            .add(SparkPlanetsPlugin)
+    }
+}
+
+/// A windowless app that spawns `scenario` on its first update and then
+/// advances sim time and Rapier by exactly `dt` per update, so runs are
+/// reproducible. Nothing records trajectories until a system is added for it.
+pub fn headless_app(scenario: Scenario, dt: f32) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugins(SimulationPlugins)
+        .add_plugin(SparkAudioPlugin { backend: AudioBackend::Null })
+
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(dt)))
+        .insert_resource(scenario)
+        .init_resource::<TrajectoryLog>()
+
+        .add_startup_system(spawn_scenario_system);
+
+    app.world.resource_mut::<RapierConfiguration>().timestep_mode = TimestepMode::Fixed {
+        dt: dt,
+        substeps: 1,
+    };
+
+    app
+}
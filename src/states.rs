@@ -0,0 +1,126 @@
+//! Top-level game states. The simulation only advances in `Flight` and
+//! `Map`; every other state freezes Rapier and sim time, and the `pause` key
+//! (Escape by default) moves between them.
+
+use bevy::{
+    prelude::*,
+    app::AppExit,
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::common::SparkSet;
+use crate::input::InputBindings;
+
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Flight,
+    Map,
+    Editor,
+    Paused,
+    /// A mission's goals, before launching into it
+    Briefing,
+    /// How a mission went, once it's over
+    Results,
+    Settings,
+}
+
+impl GameState {
+    /// Whether the simulation advances in this state.
+    pub fn is_running(self) -> bool {
+        matches!(self, GameState::Flight | GameState::Map)
+    }
+}
+
+/// The state to go back to when leaving `Paused`.
+#[derive(Resource)]
+pub struct ResumeState(pub GameState);
+
+impl Default for ResumeState {
+    fn default() -> Self {
+        ResumeState(GameState::Flight)
+    }
+}
+
+/// The menu to go back to when leaving `Settings`.
+#[derive(Resource)]
+pub struct MenuReturn(pub GameState);
+
+impl Default for MenuReturn {
+    fn default() -> Self {
+        MenuReturn(GameState::MainMenu)
+    }
+}
+
+/// Run condition for systems that play the game, like flight controls.
+pub fn simulation_running(state: Res<State<GameState>>) -> bool {
+    state.0.is_running()
+}
+
+/// Adds `GameState` and gates orbit fitting, navigation and forces on it.
+/// Without this plugin, as in headless runs, the simulation always runs.
+/// Needs `SparkInputPlugin`.
+pub struct SparkStatesPlugin;
+
+impl Plugin for SparkStatesPlugin {
+    fn build(&self, app: &mut App) {
+        SparkSet::configure(app);
+
+        app
+            .add_state::<GameState>()
+            .init_resource::<ResumeState>()
+            .init_resource::<MenuReturn>()
+            .configure_set(SparkSet::Orbits.run_if(simulation_running))
+            .configure_set(SparkSet::Navigation.run_if(simulation_running))
+            .configure_set(SparkSet::Gravity.run_if(simulation_running))
+            .configure_set(SparkSet::Forces.run_if(simulation_running))
+            .add_system(freeze_simulation.run_if(state_changed::<GameState>()))
+            .add_system(escape_key);
+    }
+}
+
+/// Stops Rapier stepping and sim time advancing outside the running states.
+pub fn freeze_simulation(
+    state: Res<State<GameState>>,
+    mut time: ResMut<Time>,
+    mut rapier: ResMut<RapierConfiguration>,
+) {
+    let running = state.0.is_running();
+    rapier.physics_pipeline_active = running;
+    if running {
+        time.unpause();
+    } else {
+        time.pause();
+    }
+    info!("State: {:?}", state.0);
+}
+
+/// The `pause` key pauses and resumes play, goes back to the main menu from
+/// the other screens, and quits from the main menu.
+pub fn escape_key(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    state: Res<State<GameState>>,
+    mut resume: ResMut<ResumeState>,
+    menu: Res<MenuReturn>,
+    mut next: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !bindings.just_pressed(&keys, "pause") {
+        return;
+    }
+    match state.0 {
+        GameState::Flight | GameState::Map => {
+            resume.0 = state.0;
+            next.set(GameState::Paused);
+        },
+        GameState::Paused => next.set(resume.0),
+        GameState::Settings => next.set(menu.0),
+        GameState::Editor | GameState::Briefing | GameState::Results => next.set(GameState::MainMenu),
+        GameState::MainMenu => {
+            info!("Exiting");
+            exit.send(AppExit);
+        },
+    }
+}
@@ -1,3 +0,0 @@
-//pub mod control;
-
-pub mod physics;
@@ -0,0 +1,41 @@
+use spark::{
+    audio::{Alarm, AudioSettings, PlayedSounds, Sound},
+    scenario::Scenario,
+    ships::tiles::TileKind,
+    simulation::headless_app,
+};
+
+/// The default scenario with the player's tanks drained, so it should be
+/// sounding the low fuel alarm from the start.
+fn empty_tanks() -> Scenario {
+    let mut scenario = Scenario::load("config/scenarios/default.ron").unwrap();
+    for ship in scenario.ships.iter_mut().filter(|ship| ship.player) {
+        for tile in ship.tiles.iter_mut().filter(|tile| tile.kind == TileKind::FuelTank) {
+            tile.fuel = Some(0.0);
+        }
+    }
+    scenario
+}
+
+#[test]
+fn empty_tanks_sound_the_fuel_alarm() {
+    let mut app = headless_app(empty_tanks(), 1.0 / 60.0);
+    for _ in 0..60 {
+        app.update();
+    }
+
+    let played = app.world.resource::<PlayedSounds>();
+    assert!(played.cues.iter().any(|cue| cue.sound == Sound::Alarm(Alarm::LowFuel)), "{:?}", played.cues);
+}
+
+#[test]
+fn muted_alarms_play_nothing() {
+    let mut app = headless_app(empty_tanks(), 1.0 / 60.0);
+    app.world.resource_mut::<AudioSettings>().muted = true;
+    for _ in 0..60 {
+        app.update();
+    }
+
+    let played = app.world.resource::<PlayedSounds>();
+    assert!(played.cues.is_empty(), "{:?}", played.cues);
+}
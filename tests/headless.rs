@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+use spark::{
+    planets::planet::Planet,
+    scenario::Scenario,
+    ships::ship::Player,
+    simulation::headless_app,
+};
+
+/// The default scenario's player orbits between r = 50 and r = 72 with a
+/// period of about 7.3s; half a minute covers four orbits.
+#[test]
+fn default_orbit_stays_bounded() {
+    let scenario = Scenario::load("config/scenarios/default.ron").unwrap();
+    let mut app = headless_app(scenario, 1.0 / 60.0);
+    // Spawn, then place the player on its orbit
+    app.update();
+    app.update();
+
+    let mut closest = f32::INFINITY;
+    let mut farthest = 0.0f32;
+    for _ in 0..(30 * 60) {
+        app.update();
+        let planet = app.world.query_filtered::<&GlobalTransform, With<Planet>>()
+            .single(&app.world).translation();
+        let ship = app.world.query_filtered::<&GlobalTransform, With<Player>>()
+            .single(&app.world).translation();
+        let distance = planet.distance(ship);
+        closest = closest.min(distance);
+        farthest = farthest.max(distance);
+    }
+
+    assert!(closest > 45.0, "fell to r = {}", closest);
+    assert!(farthest < 80.0, "climbed to r = {}", farthest);
+}